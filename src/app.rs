@@ -1,218 +1,1058 @@
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
 };
 use std::io;
 use std::io::{stdout, Write};
 use tui::{
     backend::{Backend},
+    layout::Rect,
     Terminal,
 };
-use walkdir::WalkDir;
-use std::ffi::OsStr;
+use std::path::Path;
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use std::thread;
-use arboard::Clipboard;
+use std::time::Duration;
+use unicode_width::UnicodeWidthChar;
 
-use crate::view::{App, ui};
-use crate::key::InputMode;
-use crate::note::Note;
+use crate::view::{App, Choice, ConfirmDialog, Confirmed, Leave, Popup, ui};
+use crate::key::{Action, InputMode};
+use crate::note::{check_name, language_name, load_all_markdown, load_templates, Note};
 use crate::git::{git_add_all, git_commit, git_pull, git_push};
+use crate::editor::{edit_file_with_terminal_suspended, edit_with_terminal_suspended};
+use crate::input::TextCursor;
+use crate::clipboard::{ClipHistory, Clipboard};
+use crate::state::state_file;
+use crate::vim::{self, VimMode, VimOutcome};
+use crate::palette::{palette_entries, PaletteState};
+use crate::runner::Run;
+use crate::placeholder::{FillForm, Filled, PlaceholderMemory};
 
 
-fn update_input_buffer(app: &mut App, buffer: &mut String, key: &KeyEvent, clipboard: &mut Clipboard) {
-    match key {
-        KeyEvent {code: KeyCode::Char('u'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-            // clear buffer
-            buffer.clear();
+// apply a text editing action to the focused field
+fn update_input_buffer(app: &mut App, buffer: &mut String, action: Action, clipboard: &mut Clipboard) {
+    match action {
+        Action::ClearField => app.cursor.clear(buffer),
+        Action::DeleteBackward => app.cursor.backspace(buffer),
+        Action::DeleteForward => app.cursor.delete(buffer),
+        Action::InsertNewline => app.cursor.insert(buffer, "\n"),
+        Action::SelectAll => app.cursor.select_all(buffer),
+        Action::CursorLeft | Action::SelectLeft => app.cursor.move_left(buffer, action == Action::SelectLeft),
+        Action::CursorRight | Action::SelectRight => app.cursor.move_right(buffer, action == Action::SelectRight),
+        Action::CursorUp | Action::SelectUp => app.cursor.move_up(buffer, action == Action::SelectUp),
+        Action::CursorDown | Action::SelectDown => app.cursor.move_down(buffer, action == Action::SelectDown),
+        Action::CursorHome | Action::SelectHome => app.cursor.move_home(buffer, action == Action::SelectHome),
+        Action::CursorEnd | Action::SelectEnd => app.cursor.move_end(buffer, action == Action::SelectEnd),
+        Action::Copy => {
+            // copy the selection, or the whole field without one
+            let text = app.cursor.selected_text(buffer).unwrap_or_else(|| buffer.clone());
+            copy_text(app, clipboard, &text);
         }
-        KeyEvent {code: KeyCode::Enter, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-        //      let input:String = app.input.drain(..).collect();
-        //      app.list.items.push(Note{title: input.clone(), language:"python".to_string(), contents: input.clone()} );
+        Action::Cut => {
+            if let Some(text) = app.cursor.cut(buffer) {
+                copy_text(app, clipboard, &text);
+            }
         }
-        KeyEvent {code: KeyCode::Char(c), modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-            buffer.push(*c);
+        Action::Paste => {
+            match clipboard.get_text() {
+                Ok(text) => paste_into_buffer(app, buffer, &text),
+                Err(err) => app.status = format!("Paste failed: {}", err)
+            }
         }
-        KeyEvent {code: KeyCode::Char(c), modifiers: KeyModifiers::SHIFT, kind: _pressed, state: _none} => {
-            buffer.push(*c);
+        _ => {}
+    }
+}
+
+fn copy_text(app: &mut App, clipboard: &mut Clipboard, text: &str) {
+    app.clip_history.push(text);
+    app.status = match clipboard.set_text(text) {
+        Ok(via) => format!("Copied via {}", via),
+        Err(err) => err
+    };
+}
+
+// keys of the clipboard history picker
+fn update_clip_history(app: &mut App, selected: usize, key: &KeyEvent, clipboard: &mut Clipboard) {
+    let count = app.clip_history.entries().len();
+    match key.code {
+        KeyCode::Up => app.popup = Some(Popup::ClipHistory(selected.saturating_sub(1))),
+        KeyCode::Down => app.popup = Some(Popup::ClipHistory((selected + 1).min(count.saturating_sub(1)))),
+        KeyCode::Enter => {
+            app.popup = None;
+            if let Some(text) = app.clip_history.entries().get(selected).cloned() {
+                copy_text(app, clipboard, &text);
+            }
         }
-        KeyEvent {code: KeyCode::Backspace, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-            buffer.pop();
+        KeyCode::Esc => app.popup = None,
+        _ => {}
+    }
+}
+
+// insert pasted text at the cursor, only the contents may span several lines
+fn paste_into_buffer(app: &mut App, buffer: &mut String, text: &str) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = match app.input_mode {
+        InputMode::EditingCode => text,
+        _ => text.replace('\n', " ")
+    };
+    app.cursor.insert(buffer, &text);
+}
+
+// keeps the note being edited selected in a list that changed, nothing when it is gone
+fn select_saved(app: &mut App) {
+    match app.list.items.iter().position(|item| item.file_path == app.saved.file_path) {
+        Some(index) => app.list.set_selected_num(index),
+        None => app.list.unselect()
+    }
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+// char index under the mouse, positions outside of `area` are clamped to it
+fn position_at(text: &str, area: Rect, scroll: u16, column: u16, row: u16) -> usize {
+    let line = (row.clamp(area.y, (area.y + area.height).saturating_sub(1)) - area.y + scroll) as usize;
+    let width = column.saturating_sub(area.x) as usize;
+    let col = text.split('\n').nth(line).map(|l| {
+        let mut used = 0;
+        l.chars().take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        }).count()
+    }).unwrap_or(usize::MAX);
+    TextCursor::index_of(text, line, col)
+}
+
+fn select_list_item(app: &mut App, note: &mut Note, index: usize) {
+    app.list.set_selected_num(index);
+    *note = app.list.items[index].clone();
+    app.saved = note.clone();
+    app.content_scroll = 0;
+}
+
+// clicks focus panes and select items, the wheel scrolls, dragging selects text
+// returns the list item to move to, which may need confirming first
+fn update_mouse(app: &mut App, note: &Note, search_text: &str, mouse: &MouseEvent) -> Option<Leave> {
+    let areas = app.areas;
+    let (column, row) = (mouse.column, mouse.row);
+    match mouse.kind {
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp if contains(areas.list, column, row) && !app.list.items.is_empty() => {
+            let index = match mouse.kind {
+                MouseEventKind::ScrollDown => app.list.next_index(),
+                _ => app.list.previous_index()
+            };
+            return Some(Leave::Select(index));
         }
-        KeyEvent {code: KeyCode::Tab, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {app.input_mode = app.input_mode.next_mode();}
-        KeyEvent {code: KeyCode::Esc, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-            app.input_mode = InputMode::Normal;
+        MouseEventKind::ScrollDown if contains(areas.contents, column, row) => {
+            let lines = note.contents.split('\n').count() as u16;
+            app.content_scroll = (app.content_scroll + 1).min(lines.saturating_sub(1));
+            // while editing the cursor stays in view, or drawing would scroll back to it
+            if app.input_mode == InputMode::EditingCode && (app.cursor.line_col(&note.contents).0 as u16) < app.content_scroll {
+                app.cursor.move_down(&note.contents, false);
+            }
         }
-        KeyEvent {code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-            // copy
-            clipboard.set_text(buffer.as_str()).unwrap();
+        MouseEventKind::ScrollUp if contains(areas.contents, column, row) => {
+            app.content_scroll = app.content_scroll.saturating_sub(1);
+            if app.input_mode == InputMode::EditingCode && app.cursor.line_col(&note.contents).0 as u16 >= app.content_scroll + areas.contents.height.max(1) {
+                app.cursor.move_up(&note.contents, false);
+            }
         }
-        KeyEvent {code: KeyCode::Char('v'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-            // paste
-            buffer.clear();
-            buffer.push_str(clipboard.get_text().unwrap().as_str());
+        MouseEventKind::Down(MouseButton::Left) => {
+            if contains(areas.list, column, row) {
+                app.set_mode(InputMode::Normal);
+                return app.list.item_at((row - areas.list.y) as usize).map(Leave::Select);
+            }
+            let mode = [
+                (areas.search, InputMode::EditingSearch),
+                (areas.title, InputMode::EditingTitle),
+                (areas.language, InputMode::EditingLanguage),
+                (areas.contents, InputMode::EditingCode),
+            ].into_iter().find(|(area, _)| contains(*area, column, row)).map(|(_, mode)| mode);
+            if let Some(mode) = mode {
+                if mode != app.input_mode {
+                    app.set_mode(mode);
+                }
+                if let Some((buffer, area, scroll)) = focused_buffer(app, note, search_text) {
+                    let position = position_at(buffer, area, scroll, column, row);
+                    app.cursor.move_to(buffer, position, false);
+                }
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if let Some((buffer, area, scroll)) = focused_buffer(app, note, search_text) {
+                let position = position_at(buffer, area, scroll, column, row);
+                app.cursor.move_to(buffer, position, true);
+            }
         }
         _ => {}
     }
+    None
 }
 
-fn refresh_ui() {
-    stdout().flush().unwrap();
+// the text edited in `mode`
+fn focused_field<'a>(mode: InputMode, note: &'a mut Note, search_text: &'a mut String) -> Option<&'a mut String> {
+    match mode {
+        InputMode::Normal => None,
+        InputMode::EditingSearch => Some(search_text),
+        InputMode::EditingTitle => Some(&mut note.title),
+        InputMode::EditingLanguage => Some(&mut note.language),
+        InputMode::EditingCode => Some(&mut note.contents),
+    }
 }
 
-fn load_all_markdown(base_url: &str) -> Vec<Note> {
-    let mut vecs: Vec<Note> = vec![];
-    for entry in WalkDir::new(base_url) {
-        let entry = entry.unwrap();
-        let file_name = entry.path();
-        let file_extension = file_name.extension().and_then(OsStr::to_str);
-        if  file_extension == Some("md") {
-            
-            let note = match Note::load(&file_name.display().to_string()){
-                Ok(note)  => note,
-                Err(_) => panic!("load error")
-            };
-            vecs.push(note);
-        }
+// text, area and scroll offset of the focused field
+fn focused_buffer<'a>(app: &App, note: &'a Note, search_text: &'a str) -> Option<(&'a str, Rect, u16)> {
+    match app.input_mode {
+        InputMode::Normal => None,
+        InputMode::EditingSearch => Some((search_text, app.areas.search, 0)),
+        InputMode::EditingTitle => Some((&note.title, app.areas.title, 0)),
+        InputMode::EditingLanguage => Some((&note.language, app.areas.language, 0)),
+        InputMode::EditingCode => Some((&note.contents, app.areas.contents, app.content_scroll)),
     }
-    vecs
 }
 
-#[allow(unused_variables)]
-pub fn run_app<B: Backend>(base_url: &str, terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
-    // init git
-    println!("");
-    println!("  Sync with remote server...");
-    git_pull(base_url);
+fn refresh_ui() {
+    stdout().flush().unwrap();
+}
 
-    // init clipboard
-    let mut clipboard = Clipboard::new().expect("cannot open clipboard");
+// state of the running app besides what `App` keeps for drawing
+struct Session {
+    app: App,
+    note: Note,
+    search_text: String,
+    clipboard: Clipboard,
+    matcher: SkimMatcherV2,
+    // commits and pushes of saved snippets still running
+    git: Vec<thread::JoinHandle<()>>,
+}
 
-    let matcher = SkimMatcherV2::default();
-    let mut note = Note::new(base_url, "", "", "");
-    app.list.items = load_all_markdown(base_url);
-    let mut search_text = String::new();
-    loop {
-        terminal.draw(|f| ui(f, &mut app, &note, &search_text))?;
-
-        if let Event::Key(key) = event::read()? {
-            // adjust mode
-            match app.input_mode {
-                InputMode::Normal => match key {
-                    KeyEvent {code: KeyCode::Enter, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-                        app.input_mode = InputMode::EditingTitle;
+impl Session {
+    // returns true when the app should quit
+    fn handle_event<B: Backend>(&mut self, terminal: &mut Terminal<B>, event: Event) -> io::Result<bool> {
+        match event {
+            // an open popup takes the paste when it has a text field, the note behind it never does
+            Event::Paste(text) => match &mut self.app.popup {
+                None => {
+                    if let Some(buffer) = focused_field(self.app.input_mode, &mut self.note, &mut self.search_text) {
+                        paste_into_buffer(&mut self.app, buffer, &text);
                     }
-                    KeyEvent {code: KeyCode::Tab, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {app.input_mode = app.input_mode.next_mode();}
-                    KeyEvent {code: KeyCode::Char('q'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => return Ok(()),
-                    KeyEvent {code: KeyCode::Char('n'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-                        // create new note
-                        note = Note::new(base_url, "", "", "");
-                        app.list.items.push(note.clone()); 
-                        app.list.set_selected_num(app.list.items.len() - 1); // select last new item
-                        refresh_ui();
+                }
+                Some(Popup::Fill(form)) => form.paste(&text),
+                Some(Popup::Palette(palette)) => {
+                    palette.query.push_str(text.trim_end_matches(['\r', '\n']));
+                    palette.selected = 0;
+                }
+                Some(_) => {}
+            },
+            Event::Mouse(mouse) if self.app.popup.is_none() => {
+                if let Some(to) = update_mouse(&mut self.app, &self.note, &self.search_text, &mouse) {
+                    return self.leave(to);
+                }
+            }
+            Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. }) => {
+                if let Some(Popup::Confirm(dialog)) = &self.app.popup {
+                    let clicked = dialog.buttons(self.app.areas.screen)
+                        .into_iter()
+                        .position(|button| contains(button, column, row))
+                        .map(|i| dialog.choices[i].outcome);
+                    if let Some(outcome) = clicked {
+                        self.app.popup = None;
+                        return self.confirmed(terminal, outcome);
                     }
-                    KeyEvent {code: KeyCode::Char('d'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-                        // delete note
-                        if let Some(num) = app.list.get_selected_num() {
-                            app.list.items[num].delete().expect("deleted file do not exist");
-                            app.list.delete(num);
-                            match num > 0 {
-                                true => {
-                                    app.list.set_selected_num(num - 1);
-                                    note = app.list.items[num - 1].clone();
-                                } // select last new item
-                                false => {
-                                    app.list.set_selected_num(0);
-                                    note = Note::new(base_url, "", "", "");
-                                }
+                }
+            }
+            Event::Key(key) if self.app.popup.is_some() => {
+                self.app.status.clear();
+                match self.app.popup {
+                    Some(Popup::ClipHistory(selected)) => update_clip_history(&mut self.app, selected, &key, &mut self.clipboard),
+                    Some(Popup::Palette(_)) => return self.update_palette(terminal, &key),
+                    Some(Popup::Help(scroll)) => match key.code {
+                        KeyCode::Up => self.app.popup = Some(Popup::Help(scroll.saturating_sub(1))),
+                        KeyCode::Down => self.app.popup = Some(Popup::Help(scroll + 1)),
+                        _ => self.app.popup = None
+                    },
+                    Some(Popup::Libraries(selected)) => {
+                        let choices = self.app.library_choices();
+                        match key.code {
+                            KeyCode::Up => self.app.popup = Some(Popup::Libraries(selected.saturating_sub(1))),
+                            KeyCode::Down => self.app.popup = Some(Popup::Libraries((selected + 1).min(choices.len() - 1))),
+                            KeyCode::Enter => {
+                                self.app.popup = None;
+                                return self.leave(Leave::Library(choices[selected]));
                             }
+                            KeyCode::Esc => self.app.popup = None,
+                            _ => {}
                         }
-                        refresh_ui();
                     }
-                    KeyEvent {code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-                        match app.list.get_selected_num() {
-                            Some(index) => {
-                                clipboard.set_text(note.contents.as_str()).unwrap();
-                            },
-                            None => {}
+                    Some(Popup::Fill(ref mut form)) => match form.handle_key(&key) {
+                        Some(Filled::Done(text)) => {
+                            let values = form.values();
+                            self.app.popup = None;
+                            self.app.placeholders.remember(&values);
+                            copy_text(&mut self.app, &mut self.clipboard, &text);
                         }
+                        Some(Filled::Cancel) => self.app.popup = None,
+                        None => {}
                     },
-                    KeyEvent {code: KeyCode::Char('s'), modifiers: KeyModifiers::CONTROL, kind: _pressed, state: _none} => {
-                        // save
-                        match app.list.get_selected_num() {
-                            Some(index) => {
-                                match app.list.items[index].delete() {
-									Ok(_) => {},
-									Err(_) => {}
-								};//.expect("deleted file do not exist");
-                                app.list.items[index] = Note::new(base_url, &note.language, &note.title, &note.contents);
-                                if let Ok(path) = app.list.items[index].save() {
-                                    let commit_contents = format!("update: {}, {}", note.language, note.title);
-                                    let path = base_url.to_string();
-                                    thread::spawn(move || {
-                                        git_add_all(&path);
-                                        git_commit(&path, &commit_contents);
-                                        git_push(&path);
-
-                                    });
-                                }
-                            },
-                            None => {}
+                    Some(Popup::Problems(selected)) => match key.code {
+                        KeyCode::Up => self.app.popup = Some(Popup::Problems(selected.saturating_sub(1))),
+                        KeyCode::Down => self.app.popup = Some(Popup::Problems((selected + 1).min(self.app.problems.len().saturating_sub(1)))),
+                        KeyCode::Enter => self.fix_problem(terminal, selected)?,
+                        KeyCode::Esc => self.app.popup = None,
+                        _ => {}
+                    },
+                    Some(Popup::Templates(selected)) => match key.code {
+                        KeyCode::Up => self.app.popup = Some(Popup::Templates(selected.saturating_sub(1))),
+                        KeyCode::Down => self.app.popup = Some(Popup::Templates((selected + 1).min(self.app.templates.len()))),
+                        KeyCode::Enter => {
+                            self.app.popup = None;
+                            self.new_note(selected.checked_sub(1));
+                        }
+                        KeyCode::Esc => self.app.popup = None,
+                        _ => {}
+                    },
+                    Some(Popup::Output(back)) => {
+                        let running = self.app.run.as_ref().is_some_and(|run| run.running());
+                        let count = self.app.run.as_ref().map_or(0, |run| run.lines.len()) as u16;
+                        match key.code {
+                            KeyCode::Up => self.app.popup = Some(Popup::Output((back + 1).min(count))),
+                            KeyCode::Down => self.app.popup = Some(Popup::Output(back.saturating_sub(1))),
+                            KeyCode::PageUp => self.app.popup = Some(Popup::Output((back + 10).min(count))),
+                            KeyCode::PageDown => self.app.popup = Some(Popup::Output(back.saturating_sub(10))),
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) && running => self.cancel_run(),
+                            KeyCode::Esc if running => self.cancel_run(),
+                            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') if !running => {
+                                self.app.popup = None;
+                                self.app.run = None;
+                            }
+                            _ => {}
                         }
-                        refresh_ui();
                     }
-                    KeyEvent {code: KeyCode::Left, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none } => {
-                        app.list.unselect();
+                    Some(Popup::Confirm(ref mut dialog)) => {
+                        if let Some(outcome) = dialog.handle_key(&key) {
+                            self.app.popup = None;
+                            return self.confirmed(terminal, outcome);
+                        }
                     }
-                    KeyEvent {code: KeyCode::Down, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-                        app.list.next();
-                        match app.list.get_selected_num() {
-                            Some(index) => note = app.list.items[index].clone(),
-                            None => {}
+                    None => {}
+                }
+            }
+            Event::Key(key) => {
+                self.app.status.clear();
+                let mode = self.app.input_mode;
+                if self.app.settings.vim_mode {
+                    let outcome = match mode {
+                        InputMode::EditingCode => self.app.vim.handle_key(&key, &mut self.note.contents, &mut self.app.cursor),
+                        _ => self.app.vim.handle_command_key(&key)
+                    };
+                    match outcome {
+                        VimOutcome::Unhandled => {}
+                        VimOutcome::Handled => return Ok(false),
+                        VimOutcome::Yank(text) => {
+                            copy_text(&mut self.app, &mut self.clipboard, &text);
+                            return Ok(false);
+                        }
+                        VimOutcome::Paste(below) => {
+                            match self.clipboard.get_text() {
+                                Ok(text) => vim::paste(&mut self.note.contents, &mut self.app.cursor, &text, below),
+                                Err(err) => self.app.status = format!("Paste failed: {}", err)
+                            }
+                            return Ok(false);
+                        }
+                        VimOutcome::Perform(actions) => {
+                            for action in actions {
+                                if self.perform(terminal, action)? {
+                                    return Ok(true);
+                                }
+                                // a dialog holds back the rest, as in `:wq` onto an existing title
+                                if self.app.popup.is_some() {
+                                    break;
+                                }
+                            }
+                            return Ok(false);
+                        }
+                        VimOutcome::Error(err) => {
+                            self.app.status = err;
+                            return Ok(false);
                         }
                     }
-                    KeyEvent {code: KeyCode::Up, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-                        app.list.previous();
-                        match app.list.get_selected_num() {
-                            Some(index) => note = app.list.items[index].clone(),
-                            None => {}
+                }
+                match self.app.keymap.action(mode, &key) {
+                    Some(action) => {
+                        if self.perform(terminal, action)? {
+                            return Ok(true);
                         }
                     }
-                    _ => {}
+                    None => {
+                        if let KeyEvent {code: KeyCode::Char(c), modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT, ..} = key {
+                            if let Some(buffer) = focused_field(mode, &mut self.note, &mut self.search_text) {
+                                self.app.cursor.insert(buffer, &c.to_string());
+                            }
+                        }
+                    }
+                }
+                if mode == InputMode::EditingSearch && self.search_text.is_empty() {
+                    self.app.list.items = self.load_notes();
+                    select_saved(&mut self.app);
+                }
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
 
+    // keys of the command palette, returns true when the app should quit
+    fn update_palette<B: Backend>(&mut self, terminal: &mut Terminal<B>, key: &KeyEvent) -> io::Result<bool> {
+        let palette = match &mut self.app.popup {
+            Some(Popup::Palette(palette)) => palette,
+            _ => return Ok(false)
+        };
+        let entries = palette_entries(&self.app.keymap, self.app.input_mode, self.app.settings.vim_mode, &palette.query);
+        match key.code {
+            KeyCode::Esc => self.app.popup = None,
+            KeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Down => palette.selected = (palette.selected + 1).min(entries.len().saturating_sub(1)),
+            KeyCode::Backspace => {
+                palette.query.pop();
+                palette.selected = 0;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                palette.query.push(c);
+                palette.selected = 0;
+            }
+            KeyCode::Enter => {
+                let action = entries.get(palette.selected).map(|(action, _)| *action);
+                self.app.popup = None;
+                if let Some(action) = action {
+                    return self.perform(terminal, action);
                 }
-                InputMode::EditingSearch => {
-                    update_input_buffer(&mut app, &mut search_text, &key, &mut clipboard);
-                    match key {
-                        KeyEvent {code: KeyCode::Enter, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-                            app.list.items = app.list.items.iter().enumerate().filter_map(|(i, x)| 
-                                match matcher.fuzzy_indices(&x.title, &search_text) {
-                                    Some((score, indices)) => Some(app.list.items[i].clone()),
-                                    None => None
-                                }
-                            ).collect();
-                        }
-                        _ => {}
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    // runs the choice picked in a confirm dialog, returns true when the app should quit
+    fn confirmed<B: Backend>(&mut self, _terminal: &mut Terminal<B>, outcome: Confirmed) -> io::Result<bool> {
+        match outcome {
+            Confirmed::Cancel => {}
+            Confirmed::DeleteNote => self.delete_selected(),
+            Confirmed::ClearField => {
+                if let Some(buffer) = focused_field(self.app.input_mode, &mut self.note, &mut self.search_text) {
+                    update_input_buffer(&mut self.app, buffer, Action::ClearField, &mut self.clipboard);
+                }
+            }
+            Confirmed::OverwriteNote(then) => {
+                if self.save_note() {
+                    if let Some(to) = then {
+                        return Ok(self.go(to));
                     }
-                    if search_text.len() == 0 {
-                        app.list.items = load_all_markdown(base_url);
+                }
+            }
+            Confirmed::SaveAndLeave(to) => return self.save(Some(to)),
+            Confirmed::DiscardAndLeave(to) => return Ok(self.go(to)),
+            Confirmed::RunNote => self.start_run(),
+        }
+        Ok(false)
+    }
+
+    fn runner(&self) -> Option<String> {
+        self.app.runners.get(&language_name(&self.note.language)).cloned()
+    }
+
+    // asks before running anything, snippets can do a lot of damage
+    fn confirm_run(&mut self) {
+        if self.note.contents.trim().is_empty() {
+            return;
+        }
+        let command = match self.runner() {
+            Some(command) => command,
+            None => {
+                self.app.status = format!("No runner for '{}', add one to [runners] in the config file", self.note.language);
+                return;
+            }
+        };
+        let message = format!("Run '{}' with `{}`?", self.note.title, command);
+        self.app.popup = Some(Popup::Confirm(ConfirmDialog::new("Run snippet", &message, vec![
+            Choice::new("Run", 'r', Confirmed::RunNote),
+            Choice::new("Cancel", 'c', Confirmed::Cancel),
+        ])));
+    }
+
+    fn start_run(&mut self) {
+        let command = match self.runner() {
+            Some(command) => command,
+            None => return
+        };
+        match Run::start(&command, &self.note.contents) {
+            Ok(run) => {
+                self.app.run = Some(run);
+                self.app.popup = Some(Popup::Output(0));
+            }
+            Err(err) => self.app.status = format!("Cannot run `{}`: {}", command, err)
+        }
+    }
+
+    fn cancel_run(&mut self) {
+        if let Some(run) = &mut self.app.run {
+            run.cancel();
+            self.app.status = run.summary();
+        }
+    }
+
+    // collects the output of a running snippet, true while it runs
+    fn poll_run(&mut self) -> bool {
+        let run = match &mut self.app.run {
+            Some(run) if run.running() => run,
+            _ => return false
+        };
+        if run.poll() {
+            self.app.status = run.summary();
+            return false;
+        }
+        true
+    }
+
+    // moves away from the current note, asking what to do with unsaved changes
+    fn leave(&mut self, to: Leave) -> io::Result<bool> {
+        if !self.app.is_modified(&self.note) {
+            return Ok(self.go(to));
+        }
+        if self.app.settings.autosave_on_leave {
+            return self.save(Some(to));
+        }
+        let title = match self.note.title.is_empty() {
+            true => String::from("this snippet"),
+            false => format!("'{}'", self.note.title)
+        };
+        let message = format!("Save the changes to {}?", title);
+        self.app.popup = Some(Popup::Confirm(ConfirmDialog::new("Unsaved changes", &message, vec![
+            Choice::new("Save", 's', Confirmed::SaveAndLeave(to)),
+            Choice::new("Discard", 'd', Confirmed::DiscardAndLeave(to)),
+            Choice::new("Cancel", 'c', Confirmed::Cancel),
+        ])));
+        Ok(false)
+    }
+
+    // returns true when the app should quit
+    fn go(&mut self, to: Leave) -> bool {
+        let (app, note) = (&mut self.app, &mut self.note);
+        match to {
+            Leave::Select(index) => select_list_item(app, note, index),
+            Leave::NewNote => {
+                app.templates = load_templates(&app.home().path);
+                match app.templates.is_empty() {
+                    true => self.new_note(None),
+                    false => {
+                        // offer the template of the language at hand first
+                        let selected = app.templates.iter()
+                            .position(|(_, template)| !note.language.is_empty() && template.language.eq_ignore_ascii_case(&note.language))
+                            .map_or(0, |i| i + 1);
+                        app.popup = Some(Popup::Templates(selected));
                     }
                 }
-                InputMode::EditingTitle => update_input_buffer(&mut app, &mut note.title, &key, &mut clipboard),
-                InputMode::EditingLanguage => update_input_buffer(&mut app, &mut note.language, &key, &mut clipboard),
-                InputMode::EditingCode => {
-                    update_input_buffer(&mut app, &mut note.contents, &key, &mut clipboard);
-                    match key {
-                        KeyEvent {code: KeyCode::Enter, modifiers: KeyModifiers::NONE, kind: _pressed, state: _none} => {
-                            note.contents.push('\n');
-                        }
-                        _ => {}
+            }
+            Leave::Library(active) => {
+                app.active = active;
+                for library in app.active_libraries() {
+                    if library.sync.enabled {
+                        git_pull(&library.path, &library.sync.remote, &library.sync.branch);
+                    }
+                }
+                app.status = format!("Switched to {}", app.library_title());
+                self.reload();
+            }
+            Leave::Quit => return true
+        }
+        false
+    }
+
+    // opens a file that failed to load as plain text, then loads the library again
+    fn fix_problem<B: Backend>(&mut self, terminal: &mut Terminal<B>, selected: usize) -> io::Result<()> {
+        let path = match self.app.problems.get(selected) {
+            Some(problem) => problem.path.clone(),
+            None => return Ok(())
+        };
+        if let Err(err) = edit_file_with_terminal_suspended(terminal, Path::new(&path), self.app.settings.mouse_capture) {
+            self.app.status = format!("Cannot edit {}: {}", path, err);
+            return Ok(());
+        }
+        self.app.list.items = self.load_notes();
+        // the list may have moved under the current note
+        select_saved(&mut self.app);
+        match self.app.problems.is_empty() {
+            true => {
+                self.app.popup = None;
+                self.app.status = String::from("Every file loads again");
+            }
+            false => self.app.popup = Some(Popup::Problems(selected.min(self.app.problems.len() - 1)))
+        }
+        Ok(())
+    }
+
+    // adds a snippet to the list, prefilled from `template`
+    fn new_note(&mut self, template: Option<usize>) {
+        let (app, note) = (&mut self.app, &mut self.note);
+        *note = Note::new(&app.home().path, "", "", "");
+        if let Some((_, template)) = template.and_then(|i| app.templates.get(i)) {
+            note.language = template.language.clone();
+            note.contents = template.contents.clone();
+            note.tags = template.tags.clone();
+        }
+        app.saved = note.clone();
+        app.list.items.push(note.clone());
+        app.list.set_selected_num(app.list.items.len() - 1); // select last new item
+        refresh_ui();
+    }
+
+    // the snippets of every listed library, files that fail to load go to `problems`
+    fn load_notes(&mut self) -> Vec<Note> {
+        let (mut notes, mut problems) = (vec![], vec![]);
+        for library in self.app.active_libraries() {
+            let (mut loaded, mut broken) = load_all_markdown(&library.path);
+            notes.append(&mut loaded);
+            problems.append(&mut broken);
+        }
+        self.app.problems = problems;
+        notes
+    }
+
+    // starts over with a blank note after the libraries changed
+    fn reload(&mut self) {
+        self.app.list.items = self.load_notes();
+        self.app.list.unselect();
+        if !self.app.problems.is_empty() {
+            let key = self.app.keymap.keys_for(InputMode::Normal, Action::ShowProblems).first().map(|key| key.to_string());
+            self.app.status = format!("{} files could not be loaded, {} shows them", self.app.problems.len(), key.as_deref().unwrap_or("show_problems"));
+        }
+        self.note = Note::new(&self.app.home().path, "", "", "");
+        self.app.saved = self.note.clone();
+        self.search_text.clear();
+        self.app.content_scroll = 0;
+    }
+
+    // saves unless the title is missing or taken, then moves on to `then`
+    fn save(&mut self, then: Option<Leave>) -> io::Result<bool> {
+        let (app, note) = (&mut self.app, &mut self.note);
+        // the title and language become the file and folder of the snippet
+        if let Err(err) = check_name("title", &note.title).and_then(|_| check_name("language", &note.language)) {
+            app.status = err;
+            return Ok(false);
+        }
+        // renaming onto another snippet replaces it
+        let target = Note::new(&app.library_of(&app.saved).path, &note.language, &note.title, "").file_path;
+        if target != app.saved.file_path && Path::new(&target).exists() {
+            let message = format!("'{}' already exists in {}, overwrite it?", note.title, note.language);
+            app.popup = Some(Popup::Confirm(ConfirmDialog::destructive("Overwrite snippet", &message, "Overwrite", Confirmed::OverwriteNote(then))));
+            return Ok(false);
+        }
+        match (self.save_note(), then) {
+            (true, Some(to)) => Ok(self.go(to)),
+            _ => Ok(false)
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        let (app, note) = (&mut self.app, &mut self.note);
+        if let Some((num, item)) = app.list.get_selected_num().and_then(|num| Some((num, app.list.items.get(num)?))) {
+            // a new note has no file yet
+            let _ = item.delete();
+            app.status = format!("Deleted {}", item.title);
+            app.list.delete(num);
+            match num > 0 {
+                true => select_list_item(app, note, num - 1),
+                false => {
+                    app.list.set_selected_num(0);
+                    *note = Note::new(&app.home().path, "", "", "");
+                    app.saved = note.clone();
+                }
+            }
+        }
+        refresh_ui();
+    }
+
+    // writes `note` in place of its saved copy, returns true on success
+    fn save_note(&mut self) -> bool {
+        let (app, note) = (&mut self.app, &mut self.note);
+        // each snippet stays in the library it came from
+        let library = app.library_of(&app.saved).clone();
+        let saved = Note::new(&library.path, &note.language, &note.title, &note.contents);
+        if let Err(err) = saved.replace(&app.saved) {
+            app.status = format!("Save failed: {}", err);
+            return false;
+        }
+        // an overwritten note is dropped, the old copy is replaced
+        let old = app.list.items.iter().position(|item| item.file_path == app.saved.file_path);
+        let mut items = vec![];
+        for (i, item) in app.list.items.drain(..).enumerate() {
+            match Some(i) == old {
+                true => items.push(saved.clone()),
+                false if item.file_path != saved.file_path => items.push(item),
+                false => {}
+            }
+        }
+        if old.is_none() {
+            items.push(saved.clone());
+        }
+        app.list.items = items;
+        if let Some(index) = app.list.items.iter().position(|item| item.file_path == saved.file_path) {
+            app.list.set_selected_num(index);
+        }
+        *note = saved.clone();
+        app.saved = saved;
+
+        let commit_contents = format!("update: {}, {}", note.language, note.title);
+        self.git.retain(|handle| !handle.is_finished());
+        self.git.push(thread::spawn(move || {
+            git_add_all(&library.path);
+            git_commit(&library.path, &commit_contents);
+            if library.sync.enabled {
+                git_push(&library.path, &library.sync.remote, &library.sync.branch);
+            }
+        }));
+        refresh_ui();
+        true
+    }
+
+    // waits for the commits in flight, quitting must not lose them
+    fn finish_git<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        if self.git.iter().any(|handle| !handle.is_finished()) {
+            self.app.status = String::from("Waiting for git to finish...");
+            terminal.draw(|f| ui(f, &mut self.app, &self.note, &self.search_text))?;
+        }
+        for handle in self.git.drain(..) {
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+
+    // returns true when the app should quit
+    fn perform<B: Backend>(&mut self, terminal: &mut Terminal<B>, action: Action) -> io::Result<bool> {
+        let app = &mut self.app;
+        let note = &mut self.note;
+        match action {
+            Action::Quit => return self.leave(Leave::Quit),
+            Action::ForceQuit => return Ok(true),
+            Action::FocusTitle => app.set_mode(InputMode::EditingTitle),
+            Action::FocusList => app.set_mode(InputMode::Normal),
+            Action::FocusSearch => app.set_mode(InputMode::EditingSearch),
+            Action::FocusLanguage => app.set_mode(InputMode::EditingLanguage),
+            Action::FocusContents => app.set_mode(InputMode::EditingCode),
+            Action::EditContents | Action::AppendContents | Action::OpenLine => {
+                app.set_mode(InputMode::EditingCode);
+                app.vim.mode = VimMode::Insert;
+                match action {
+                    Action::EditContents => app.cursor.move_to(&note.contents, 0, false),
+                    Action::AppendContents => app.cursor.clamp(&note.contents),
+                    _ => app.cursor.insert(&mut note.contents, "\n")
+                }
+            }
+            // keys only reach the command line in vim mode
+            Action::CommandLine if app.settings.vim_mode => app.vim.start_command(),
+            Action::CommandPalette => app.popup = Some(Popup::Palette(PaletteState::new())),
+            Action::Help => app.popup = Some(Popup::Help(0)),
+            Action::NextField => app.set_mode(app.input_mode.next_mode()),
+            Action::PreviousField => app.set_mode(app.input_mode.previous_mode()),
+            Action::NewNote => return self.leave(Leave::NewNote),
+            Action::DeleteNote => {
+                if let Some(item) = app.list.get_selected_num().and_then(|num| app.list.items.get(num)) {
+                    let message = format!("Delete '{}'? Its file is removed from the repository.", item.title);
+                    app.popup = Some(Popup::Confirm(ConfirmDialog::destructive("Delete snippet", &message, "Delete", Confirmed::DeleteNote)));
+                }
+            }
+            Action::SwitchLibrary => {
+                let current = app.library_choices().iter().position(|choice| *choice == app.active).unwrap_or(0);
+                app.popup = Some(Popup::Libraries(current));
+            }
+            Action::ClipHistory => {
+                // pick from the clipboard history
+                match app.clip_history.entries().is_empty() {
+                    true => app.status = String::from("Clipboard history is empty"),
+                    false => app.popup = Some(Popup::ClipHistory(0))
+                }
+            }
+            Action::OpenEditor => {
+                // edit contents in $VISUAL/$EDITOR
+                match edit_with_terminal_suspended(terminal, &note.contents, note.language_extension(), app.settings.mouse_capture) {
+                    Ok(contents) => note.contents = contents,
+                    Err(err) => app.status = format!("Editor failed: {}", err)
+                }
+            }
+            Action::Copy if app.input_mode == InputMode::Normal => {
+                if app.list.get_selected_num().is_some() {
+                    // templates get their placeholders filled in first
+                    match FillForm::new(&note.contents, &mut app.placeholders) {
+                        Some(form) => app.popup = Some(Popup::Fill(form)),
+                        None => copy_text(app, &mut self.clipboard, &note.contents)
                     }
                 }
             }
+            Action::Run => self.confirm_run(),
+            Action::ShowProblems => match app.problems.is_empty() {
+                true => app.status = String::from("Every file loaded"),
+                false => app.popup = Some(Popup::Problems(0))
+            },
+            Action::Save => return self.save(None),
+            Action::Unselect => app.list.unselect(),
+            Action::SelectNext | Action::SelectPrevious | Action::SelectFirst | Action::SelectLast if !app.list.items.is_empty() => {
+                let index = match action {
+                    Action::SelectNext => app.list.next_index(),
+                    Action::SelectPrevious => app.list.previous_index(),
+                    Action::SelectFirst => 0,
+                    _ => app.list.items.len() - 1
+                };
+                return self.leave(Leave::Select(index));
+            }
+            Action::ClearField if app.input_mode != InputMode::EditingSearch => {
+                let empty = focused_field(app.input_mode, note, &mut self.search_text).is_none_or(|buffer| buffer.is_empty());
+                if !empty {
+                    let message = format!("Clear the {}?", match app.input_mode {
+                        InputMode::EditingTitle => "title",
+                        InputMode::EditingLanguage => "language",
+                        _ => "contents"
+                    });
+                    app.popup = Some(Popup::Confirm(ConfirmDialog::destructive("Clear field", &message, "Clear", Confirmed::ClearField)));
+                }
+            }
+            Action::ApplySearch => {
+                let matcher = &self.matcher;
+                let search_text = &self.search_text;
+                app.list.items = app.list.items.iter()
+                    .filter(|x| matcher.fuzzy_match(&x.title, search_text).is_some())
+                    .cloned()
+                    .collect();
+                select_saved(app);
+            }
+            action => {
+                if let Some(buffer) = focused_field(app.input_mode, note, &mut self.search_text) {
+                    update_input_buffer(app, buffer, action, &mut self.clipboard);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
 
+pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+    // init git
+    for library in app.active_libraries() {
+        if library.sync.enabled {
+            println!();
+            println!("  Sync {} with remote server...", library.name);
+            git_pull(&library.path, &library.sync.remote, &library.sync.branch);
+        }
+    }
+
+    // init clipboard
+    let clipboard = Clipboard::new();
+    if let Some(err) = &clipboard.init_error {
+        app.status = err.clone();
+    }
+    app.clip_history = match app.settings.persist_clip_history {
+        true => match state_file(&app.libraries[0].path, "clip_history.json") {
+            Ok(file) => ClipHistory::load(app.settings.clip_history_size, file),
+            Err(_) => ClipHistory::new(app.settings.clip_history_size)
+        },
+        false => ClipHistory::new(app.settings.clip_history_size)
+    };
+
+    app.placeholders = PlaceholderMemory::new(&app.libraries[0].path);
+
+    let mut session = Session {
+        app,
+        note: Note::new("", "", "", ""),
+        search_text: String::new(),
+        clipboard,
+        matcher: SkimMatcherV2::default(),
+        git: vec![],
+    };
+    session.reload();
+    loop {
+        let running = session.poll_run();
+        terminal.draw(|f| ui(f, &mut session.app, &session.note, &session.search_text))?;
+
+        // redraw the output of a running snippet while no keys come in
+        if running && !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        if session.handle_event(terminal, event::read()?)? {
+            return session.finish_git(terminal);
         }
     }
 }
 
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::process::Command;
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+    use fuzzy_matcher::skim::SkimMatcherV2;
+    use tui::{backend::TestBackend, Terminal};
+    use super::{select_list_item, Session};
+    use crate::clipboard::Clipboard;
+    use crate::config::{Library, SyncConfig, DEFAULT_PROFILE};
+    use crate::key::{Action, InputMode};
+    use crate::keymap::Keymap;
+    use crate::note::Note;
+    use crate::placeholder::FillForm;
+    use crate::view::{ui, App, Popup};
 
+    // a session on a fresh git repository below target/
+    fn new_session(name: &str) -> (Session, Terminal<TestBackend>) {
+        let repo = format!("./target/temp_session/{}", name);
+        let _ = fs::remove_dir_all(&repo);
+        fs::create_dir_all(&repo).unwrap();
+        for args in [vec!["init", "-q"], vec!["config", "user.name", "test"], vec!["config", "user.email", "test@example.com"]] {
+            Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap();
+        }
+        let mut app = App {
+            libraries: vec![Library { name: String::from(DEFAULT_PROFILE), path: repo.clone(), sync: SyncConfig::default() }],
+            keymap: Keymap::vim(),
+            ..Default::default()
+        };
+        app.settings.vim_mode = true;
+        let session = Session {
+            note: Note::new(&repo, "", "", ""),
+            app,
+            search_text: String::new(),
+            clipboard: Clipboard::with_backends(vec![], None),
+            matcher: SkimMatcherV2::default(),
+            git: vec![],
+        };
+        (session, Terminal::new(TestBackend::new(80, 24)).unwrap())
+    }
+
+    fn type_keys(session: &mut Session, terminal: &mut Terminal<TestBackend>, keys: &str) -> bool {
+        let mut quit = false;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c)
+            };
+            quit = session.handle_event(terminal, Event::Key(KeyEvent::new(code, KeyModifiers::NONE))).unwrap();
+        }
+        quit
+    }
+
+    fn commits(session: &Session) -> String {
+        let log = Command::new("git").arg("-C").arg(&session.app.libraries[0].path).args(["log", "--format=%s"]).output().unwrap();
+        String::from(String::from_utf8_lossy(&log.stdout))
+    }
+
+    #[test]
+    fn write_quit_commits() {
+        let (mut session, mut terminal) = new_session("wq");
+        session.note.language = String::from("sh");
+        session.note.title = String::from("list");
+        session.note.contents = String::from("ls");
+        session.app.input_mode = InputMode::EditingCode;
+        assert!(type_keys(&mut session, &mut terminal, ":wq\n"));
+        session.finish_git(&mut terminal).unwrap();
+        assert_eq!(commits(&session), "update: sh, list\n");
+    }
+
+    #[test]
+    fn save_keeps_names_inside_the_library() {
+        let (mut session, mut terminal) = new_session("save_bad_name");
+        session.note.language = String::from("sh");
+        session.note.title = String::from("../escape");
+        session.app.input_mode = InputMode::EditingCode;
+        assert!(!type_keys(&mut session, &mut terminal, ":w\n"));
+        assert!(session.app.status.contains("must not contain"), "{}", session.app.status);
+        assert!(!std::path::Path::new(&session.app.libraries[0].path).join("escape.md").exists());
+    }
+
+    #[test]
+    fn delete_after_search() {
+        let (mut session, mut terminal) = new_session("delete_after_search");
+        for title in ["alpha", "beta", "gamma"] {
+            Note::new(&session.app.libraries[0].path, "sh", title, "ls").save().unwrap();
+        }
+        session.reload();
+        let gamma = session.app.list.items.iter().position(|item| item.title == "gamma").unwrap();
+        select_list_item(&mut session.app, &mut session.note, gamma);
+        session.search_text = String::from("alpha");
+        session.perform(&mut terminal, Action::ApplySearch).unwrap();
+        assert_eq!(session.app.list.items.len(), 1);
+        assert_eq!(session.app.list.get_selected_num(), None);
+        session.perform(&mut terminal, Action::DeleteNote).unwrap();
+        assert!(session.app.popup.is_none());
+
+        select_list_item(&mut session.app, &mut session.note, 0);
+        session.perform(&mut terminal, Action::DeleteNote).unwrap();
+        assert!(session.app.popup.is_some());
+    }
+
+    #[test]
+    fn paste_goes_to_the_popup() {
+        let (mut session, mut terminal) = new_session("paste");
+        session.app.input_mode = InputMode::EditingCode;
+        session.note.contents = String::from("{{pod}}");
+        session.app.popup = Some(Popup::Fill(FillForm::new("{{pod}}", &mut session.app.placeholders).unwrap()));
+        session.handle_event(&mut terminal, Event::Paste(String::from("web-1\n"))).unwrap();
+        assert!(matches!(&session.app.popup, Some(Popup::Fill(form)) if form.values == vec!["web-1"]));
+        session.app.popup = Some(Popup::ClipHistory(0));
+        session.handle_event(&mut terminal, Event::Paste(String::from("rm -rf"))).unwrap();
+        assert_eq!(session.note.contents, "{{pod}}");
+        session.app.popup = None;
+        session.handle_event(&mut terminal, Event::Paste(String::from("ls "))).unwrap();
+        assert_eq!(session.note.contents, "{{pod}}ls ");
+    }
+
+    #[test]
+    fn wheel_scrolls_while_editing() {
+        let (mut session, mut terminal) = new_session("wheel");
+        session.note.contents = (0..50).map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+        session.app.input_mode = InputMode::EditingCode;
+        session.app.cursor.move_to(&session.note.contents, 0, false);
+        let draw = |session: &mut Session, terminal: &mut Terminal<TestBackend>| {
+            terminal.draw(|f| ui(f, &mut session.app, &session.note, &session.search_text)).unwrap();
+        };
+        draw(&mut session, &mut terminal);
+        let area = session.app.areas.contents;
+        let wheel = |kind| Event::Mouse(MouseEvent { kind, column: area.x + 1, row: area.y + 1, modifiers: KeyModifiers::NONE });
+        for _ in 0..5 {
+            session.handle_event(&mut terminal, wheel(MouseEventKind::ScrollDown)).unwrap();
+            draw(&mut session, &mut terminal);
+        }
+        assert_eq!(session.app.content_scroll, 5);
+        assert_eq!(session.app.cursor.line_col(&session.note.contents).0, 5);
+        session.handle_event(&mut terminal, wheel(MouseEventKind::ScrollUp)).unwrap();
+        draw(&mut session, &mut terminal);
+        assert_eq!(session.app.content_scroll, 4);
+    }
+
+    #[test]
+    fn help_lists_vim_keys() {
+        let (mut session, mut terminal) = new_session("vim_help");
+        session.app.input_mode = InputMode::EditingCode;
+        terminal.draw(|f| ui(f, &mut session.app, &session.note, &session.search_text)).unwrap();
+        let text = |terminal: &Terminal<TestBackend>| terminal.backend().buffer().content().iter().map(|cell| cell.symbol.as_str()).collect::<String>();
+        assert!(text(&terminal).contains(" dd  cut line"));
+        session.app.popup = Some(Popup::Help(0));
+        let mut terminal = Terminal::new(TestBackend::new(80, 160)).unwrap();
+        terminal.draw(|f| ui(f, &mut session.app, &session.note, &session.search_text)).unwrap();
+        assert!(text(&terminal).contains("Vim keys in the contents"));
+        assert!(text(&terminal).contains(":wq"));
+    }
+
+    #[test]
+    fn save_and_quit_commits() {
+        let (mut session, mut terminal) = new_session("save_and_quit");
+        session.note.language = String::from("sh");
+        session.note.title = String::from("list");
+        assert!(!type_keys(&mut session, &mut terminal, ":q\n"));
+        assert!(type_keys(&mut session, &mut terminal, "s"));
+        session.finish_git(&mut terminal).unwrap();
+        assert_eq!(commits(&session), "update: sh, list\n");
+
+        let (mut session, mut terminal) = new_session("autosave_quit");
+        session.app.settings.autosave_on_leave = true;
+        session.note.language = String::from("sh");
+        session.note.title = String::from("tree");
+        assert!(type_keys(&mut session, &mut terminal, ":q\n"));
+        session.finish_git(&mut terminal).unwrap();
+        assert_eq!(commits(&session), "update: sh, tree\n");
+    }
+}
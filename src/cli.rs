@@ -0,0 +1,499 @@
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+
+use crate::config::{Library, Theme};
+use crate::git::{git_commit_changes, git_sync};
+use crate::note::{check_name, load_all_markdown, Note};
+use crate::export;
+use crate::import::{self, Outcome};
+use crate::pick::{self, shell_init, PickerState, Shell};
+use crate::placeholder::PlaceholderMemory;
+
+// exit codes of the subcommands, clap exits with 2 on usage errors
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_AMBIGUOUS: i32 = 4;
+pub const EXIT_EXISTS: i32 = 5;
+pub const EXIT_CANCELLED: i32 = 130;
+
+// bumped when a field of the JSON output changes meaning or goes away
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Parser)]
+#[command(version, about = "Snippet manager for the terminal, synced with git")]
+pub struct Cli {
+    /// config file, by default config.toml in the platform config directory
+    #[arg(long, global = true, env = "SNIPPET_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// snippet repository, overriding the one in the config file
+    #[arg(long, global = true, env = "SNIPPET_REPO")]
+    pub repo: Option<String>,
+    /// profile to open, `all` lists the snippets of every profile
+    #[arg(long, global = true, env = "SNIPPET_PROFILE")]
+    pub profile: Option<String>,
+    /// without a command the full-screen interface starts
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// print the snippets as `language/title`
+    List {
+        #[arg(long)]
+        lang: Option<String>,
+        #[command(flatten)]
+        format: Format,
+    },
+    /// print the contents of a snippet, `language/title` picks one of several
+    Show {
+        title: String,
+        #[arg(long)]
+        lang: Option<String>,
+        #[command(flatten)]
+        format: Format,
+    },
+    /// fuzzy search the titles and languages, best match first
+    Search {
+        query: String,
+        #[command(flatten)]
+        format: Format,
+    },
+    /// save a snippet with the contents read from stdin
+    Add {
+        #[arg(long)]
+        title: String,
+        #[arg(long)]
+        lang: String,
+        /// replace a snippet with the same title and language
+        #[arg(long)]
+        force: bool,
+    },
+    /// delete a snippet
+    Rm {
+        title: String,
+        #[arg(long)]
+        lang: Option<String>,
+    },
+    /// commit local changes, pull and push
+    Sync,
+    /// import snippets from VS Code, pet or a folder of source files in one commit
+    Import {
+        /// a file or folder to import
+        path: PathBuf,
+        /// detected from the files when left out
+        #[arg(long, value_enum)]
+        format: Option<import::Format>,
+        /// only print what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// write the snippets as VS Code snippets, one Markdown document or a static site
+    Export {
+        #[arg(value_enum)]
+        format: export::Format,
+        /// a folder for vscode and html, a file for markdown
+        out: PathBuf,
+    },
+    /// choose a snippet in a picker drawn on the terminal and print it to stdout
+    Pick {
+        /// initial search text
+        query: Option<String>,
+    },
+    /// print a widget for the shell that inserts a picked snippet on alt-s
+    ShellInit {
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+pub struct Format {
+    /// print a JSON document with the snippets
+    #[arg(long, conflicts_with = "jsonl")]
+    json: bool,
+    /// print one JSON object per snippet and line
+    #[arg(long)]
+    jsonl: bool,
+}
+
+// a snippet in the JSON output, the note's own fields plus where it lives
+#[derive(Serialize)]
+struct Record<'a> {
+    id: String,
+    library: &'a str,
+    #[serde(flatten)]
+    note: &'a Note,
+    created: Option<u64>,
+    modified: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    snippets: Vec<Record<'a>>,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    version: u32,
+    #[serde(flatten)]
+    record: Record<'a>,
+}
+
+pub struct CliError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl CliError {
+    fn new(code: i32, message: String) -> Self {
+        CliError { code, message }
+    }
+}
+
+type CResult<T> = Result<T, CliError>;
+
+// runs `command` on `libraries`, new snippets go to the first one
+pub fn run(command: Command, libraries: &[Library], theme: &Theme) -> i32 {
+    let result = match command {
+        Command::List { lang, format } => list(libraries, lang.as_deref(), &format),
+        Command::Show { title, lang, format } => show(libraries, &title, lang.as_deref(), &format),
+        Command::Search { query, format } => search(libraries, &query, &format),
+        Command::Add { title, lang, force } => add(libraries, &title, &lang, force),
+        Command::Rm { title, lang } => remove(libraries, &title, lang.as_deref()),
+        Command::Sync => sync(libraries),
+        Command::Import { path, format, dry_run } => import_snippets(libraries, &path, format, dry_run),
+        Command::Export { format, out } => export_snippets(libraries, format, &out),
+        Command::Pick { query } => pick(libraries, query.as_deref().unwrap_or(""), theme),
+        Command::ShellInit { shell } => {
+            print!("{}", shell_init(shell, &program()));
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            if !err.message.is_empty() {
+                eprintln!("{}", err.message);
+            }
+            err.code
+        }
+    }
+}
+
+// files that fail to load are reported and skipped
+fn load_notes(libraries: &[Library]) -> Vec<Note> {
+    let mut notes = vec![];
+    for library in libraries {
+        let (mut loaded, problems) = load_all_markdown(&library.path);
+        for problem in problems {
+            eprintln!("Skipped {}: {}", problem.path, problem.message);
+        }
+        notes.append(&mut loaded);
+    }
+    notes
+}
+
+// the library holding `note`, judged by its path
+fn library_of<'a>(libraries: &'a [Library], note: &Note) -> &'a Library {
+    libraries.iter()
+        .filter(|library| Path::new(&note.file_path).starts_with(&library.path))
+        .max_by_key(|library| library.path.len())
+        .unwrap_or(&libraries[0])
+}
+
+fn label(libraries: &[Library], note: &Note) -> String {
+    match libraries.len() > 1 {
+        true => format!("{}:{}/{}", library_of(libraries, note).name, note.language, note.title),
+        false => format!("{}/{}", note.language, note.title)
+    }
+}
+
+// `title` may be written as `language/title`, exact matches win over case-insensitive ones
+fn find<'a>(notes: &'a [Note], title: &str, lang: Option<&str>) -> CResult<&'a Note> {
+    let (lang, title) = match (lang, title.split_once('/')) {
+        (None, Some((lang, title))) => (Some(lang), title),
+        _ => (lang, title)
+    };
+    let matches = |exact: bool| -> Vec<&Note> {
+        let same = |a: &str, b: &str| match exact {
+            true => a == b,
+            false => a.to_lowercase() == b.to_lowercase()
+        };
+        notes.iter()
+            .filter(|note| same(&note.title, title) && lang.is_none_or(|lang| same(&note.language, lang)))
+            .collect()
+    };
+    let mut found = matches(true);
+    if found.is_empty() {
+        found = matches(false);
+    }
+    match found.len() {
+        0 => Err(CliError::new(EXIT_NOT_FOUND, format!("No snippet called '{}'", title))),
+        1 => Ok(found[0]),
+        _ => {
+            let languages: Vec<&str> = found.iter().map(|note| note.language.as_str()).collect();
+            Err(CliError::new(EXIT_AMBIGUOUS, format!("'{}' exists in {}, pick one with --lang", title, languages.join(", "))))
+        }
+    }
+}
+
+fn seconds(time: io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?.duration_since(UNIX_EPOCH).ok().map(|duration| duration.as_secs())
+}
+
+fn record<'a>(libraries: &'a [Library], note: &'a Note) -> Record<'a> {
+    let library = library_of(libraries, note);
+    // the path inside the library without the extension, e.g. `sh/list`
+    let id = Path::new(&note.file_path).strip_prefix(&library.path)
+        .unwrap_or(Path::new(&note.file_path))
+        .with_extension("")
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let metadata = std::fs::metadata(&note.file_path).ok();
+    Record {
+        id,
+        library: &library.name,
+        note,
+        created: metadata.as_ref().and_then(|metadata| seconds(metadata.created())),
+        modified: metadata.as_ref().and_then(|metadata| seconds(metadata.modified())),
+    }
+}
+
+// prints `notes` as JSON when `format` asks for it, false means plain text is up to the caller
+fn print_json(libraries: &[Library], notes: &[&Note], format: &Format) -> CResult<bool> {
+    let failed = |err: io::Error| CliError::new(EXIT_ERROR, format!("Cannot write the output: {}", err));
+    let mut out = io::stdout().lock();
+    match (format.json, format.jsonl) {
+        (true, _) => {
+            let document = Document {
+                version: JSON_VERSION,
+                snippets: notes.iter().map(|note| record(libraries, note)).collect(),
+            };
+            serde_json::to_writer_pretty(&mut out, &document).map_err(io::Error::from).map_err(failed)?;
+            writeln!(out).map_err(failed)?;
+        }
+        (false, true) => {
+            // one line at a time so large libraries stream
+            for note in notes {
+                let line = Line { version: JSON_VERSION, record: record(libraries, note) };
+                serde_json::to_writer(&mut out, &line).map_err(io::Error::from).map_err(failed)?;
+                writeln!(out).map_err(failed)?;
+            }
+        }
+        (false, false) => return Ok(false)
+    }
+    Ok(true)
+}
+
+// commits the change and pushes it when the library syncs
+fn commit(library: &Library, message: &str) -> CResult<()> {
+    git_commit_changes(&library.path, message).map_err(|err| CliError::new(EXIT_ERROR, err))?;
+    match library.sync.enabled {
+        true => git_sync(&library.path, &library.sync.remote, &library.sync.branch).map_err(|err| CliError::new(EXIT_ERROR, err)),
+        false => Ok(())
+    }
+}
+
+fn list(libraries: &[Library], lang: Option<&str>, format: &Format) -> CResult<()> {
+    let mut notes = load_notes(libraries);
+    notes.retain(|note| lang.is_none_or(|lang| note.language.eq_ignore_ascii_case(lang)));
+    notes.sort_by(|a, b| (&a.language, &a.title).cmp(&(&b.language, &b.title)));
+    if print_json(libraries, &notes.iter().collect::<Vec<_>>(), format)? {
+        return Ok(());
+    }
+    for note in &notes {
+        println!("{}", label(libraries, note));
+    }
+    Ok(())
+}
+
+fn show(libraries: &[Library], title: &str, lang: Option<&str>, format: &Format) -> CResult<()> {
+    let notes = load_notes(libraries);
+    let note = find(&notes, title, lang)?;
+    if print_json(libraries, &[note], format)? {
+        return Ok(());
+    }
+    match note.contents.ends_with('\n') {
+        true => print!("{}", note.contents),
+        false => println!("{}", note.contents)
+    }
+    Ok(())
+}
+
+fn search(libraries: &[Library], query: &str, format: &Format) -> CResult<()> {
+    let matcher = SkimMatcherV2::default();
+    let notes = load_notes(libraries);
+    let mut found: Vec<(i64, &Note)> = notes.iter()
+        .filter_map(|note| {
+            let text = format!("{} {}", note.title, note.language);
+            matcher.fuzzy_match(&text, query).map(|score| (score, note))
+        })
+        .collect();
+    if found.is_empty() {
+        return Err(CliError::new(EXIT_NOT_FOUND, format!("No snippet matches '{}'", query)));
+    }
+    found.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    if print_json(libraries, &found.iter().map(|(_, note)| *note).collect::<Vec<_>>(), format)? {
+        return Ok(());
+    }
+    for (_, note) in found {
+        println!("{}", label(libraries, note));
+    }
+    Ok(())
+}
+
+fn add(libraries: &[Library], title: &str, lang: &str, force: bool) -> CResult<()> {
+    check_name("title", title).and_then(|_| check_name("language", lang)).map_err(|err| CliError::new(EXIT_USAGE, err))?;
+    let library = &libraries[0];
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents).map_err(|err| CliError::new(EXIT_ERROR, format!("Cannot read stdin: {}", err)))?;
+    let note = Note::new(&library.path, lang, title, &contents);
+    if !force && Path::new(&note.file_path).exists() {
+        return Err(CliError::new(EXIT_EXISTS, format!("{}/{} already exists, replace it with --force", lang, title)));
+    }
+    note.save().map_err(|err| CliError::new(EXIT_ERROR, format!("Cannot save {}: {}", note.file_path, err)))?;
+    commit(library, &format!("update: {}, {}", lang, title))
+}
+
+fn remove(libraries: &[Library], title: &str, lang: Option<&str>) -> CResult<()> {
+    let notes = load_notes(libraries);
+    let note = find(&notes, title, lang)?;
+    note.delete().map_err(|err| CliError::new(EXIT_ERROR, format!("Cannot delete {}: {}", note.file_path, err)))?;
+    commit(library_of(libraries, note), &format!("remove: {}, {}", note.language, note.title))
+}
+
+fn sync(libraries: &[Library]) -> CResult<()> {
+    for library in libraries {
+        commit(library, "sync")?;
+        if !library.sync.enabled {
+            eprintln!("{}: sync is disabled, committed locally", library.name);
+        }
+    }
+    Ok(())
+}
+
+fn import_snippets(libraries: &[Library], path: &Path, format: Option<import::Format>, dry_run: bool) -> CResult<()> {
+    if !path.exists() {
+        return Err(CliError::new(EXIT_NOT_FOUND, format!("{} does not exist", path.display())));
+    }
+    let format = format.unwrap_or_else(|| import::detect(path));
+    let (entries, unreadable) = import::read(path, format).map_err(|err| CliError::new(EXIT_ERROR, err))?;
+    for err in &unreadable {
+        println!("! {}", err);
+    }
+    let planned = import::plan(entries, &load_notes(libraries));
+    let library = &libraries[0];
+    let (mut added, mut duplicates, mut taken) = (vec![], 0, 0);
+    for (entry, outcome) in &planned {
+        let name = format!("{}/{}", entry.language, entry.title);
+        match outcome {
+            Outcome::New => {
+                println!("+ {}", name);
+                let mut note = Note::new(&library.path, &entry.language, &entry.title, &entry.contents);
+                note.tags = entry.tags.clone();
+                added.push(note);
+            }
+            Outcome::Duplicate(same) => {
+                println!("= {} (same as {})", name, same);
+                duplicates += 1;
+            }
+            Outcome::Taken => {
+                println!("! {} (title taken, from {})", name, entry.source);
+                taken += 1;
+            }
+            Outcome::Invalid(err) => {
+                println!("! {} ({}, from {})", name, err, entry.source);
+                taken += 1;
+            }
+        }
+    }
+    // everything is written before the commit, a failure takes back what was written
+    if !dry_run {
+        for (i, note) in added.iter().enumerate() {
+            if let Err(err) = note.save() {
+                for written in &added[..i] {
+                    let _ = written.delete();
+                }
+                return Err(CliError::new(EXIT_ERROR, format!("Cannot save {}: {}, nothing was imported", note.file_path, err)));
+            }
+        }
+    }
+    let verb = match dry_run {
+        true => "Would import",
+        false => "Imported"
+    };
+    eprintln!("{} {} snippets, skipped {} duplicates, {} taken or invalid titles and {} unreadable files", verb, added.len(), duplicates, taken, unreadable.len());
+    if dry_run || added.is_empty() {
+        return Ok(());
+    }
+    let source = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+    commit(library, &format!("import: {} snippets from {}", added.len(), source))
+}
+
+fn export_snippets(libraries: &[Library], format: export::Format, out: &Path) -> CResult<()> {
+    let notes = load_notes(libraries);
+    let written = export::export(&notes, format, out).map_err(|err| CliError::new(EXIT_ERROR, err))?;
+    for path in &written {
+        println!("{}", path.display());
+    }
+    eprintln!("Exported {} snippets", notes.len());
+    Ok(())
+}
+
+fn pick(libraries: &[Library], query: &str, theme: &Theme) -> CResult<()> {
+    let notes = load_notes(libraries);
+    if notes.is_empty() {
+        return Err(CliError::new(EXIT_NOT_FOUND, String::from("No snippets to pick from")));
+    }
+    let labels = notes.iter().map(|note| label(libraries, note)).collect();
+    let mut state = PickerState::new(notes, labels, query, PlaceholderMemory::new(&libraries[0].path));
+    match pick::run(&mut state, theme) {
+        Ok(Some(text)) => {
+            print!("{}", text);
+            Ok(())
+        }
+        Ok(None) => Err(CliError::new(EXIT_CANCELLED, String::new())),
+        Err(err) => Err(CliError::new(EXIT_ERROR, format!("Cannot draw the picker: {}", err)))
+    }
+}
+
+// the path of this binary, so the widgets work without it being on PATH
+fn program() -> String {
+    std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| String::from(env!("CARGO_PKG_NAME")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find, EXIT_AMBIGUOUS, EXIT_NOT_FOUND};
+    use crate::note::Note;
+
+    fn notes() -> Vec<Note> {
+        vec![
+            Note::new("repo", "python", "hello", "print(1)"),
+            Note::new("repo", "rust", "hello", "println!()"),
+            Note::new("repo", "sh", "List", "ls"),
+        ]
+    }
+
+    #[test]
+    fn finds_by_title_and_language() {
+        let notes = notes();
+        assert_eq!(find(&notes, "list", None).ok().map(|note| note.language.as_str()), Some("sh"));
+        assert_eq!(find(&notes, "rust/hello", None).ok().map(|note| note.contents.as_str()), Some("println!()"));
+        assert_eq!(find(&notes, "hello", Some("python")).ok().map(|note| note.contents.as_str()), Some("print(1)"));
+        assert_eq!(find(&notes, "hello", None).err().map(|err| err.code), Some(EXIT_AMBIGUOUS));
+        assert_eq!(find(&notes, "missing", None).err().map(|err| err.code), Some(EXIT_NOT_FOUND));
+    }
+}
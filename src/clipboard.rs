@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+type CResult<T> = Result<T, String>;
+
+pub trait ClipboardBackend {
+    fn name(&self) -> &'static str;
+    fn set_text(&mut self, text: &str) -> CResult<()>;
+    fn get_text(&mut self) -> CResult<String>;
+}
+
+// the desktop clipboard, unavailable on headless servers and most SSH sessions
+pub struct SystemClipboard {
+    clipboard: arboard::Clipboard,
+}
+
+impl SystemClipboard {
+    pub fn new() -> CResult<Self> {
+        match arboard::Clipboard::new() {
+            Ok(clipboard) => Ok(SystemClipboard { clipboard }),
+            Err(err) => Err(format!("system clipboard unavailable: {}", err))
+        }
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn name(&self) -> &'static str {
+        "system clipboard"
+    }
+
+    fn set_text(&mut self, text: &str) -> CResult<()> {
+        self.clipboard.set_text(text).map_err(|err| err.to_string())
+    }
+
+    fn get_text(&mut self) -> CResult<String> {
+        self.clipboard.get_text().map_err(|err| err.to_string())
+    }
+}
+
+// asks the terminal emulator to set its clipboard, works through SSH
+pub struct Osc52Clipboard;
+
+impl ClipboardBackend for Osc52Clipboard {
+    fn name(&self) -> &'static str {
+        "OSC 52"
+    }
+
+    fn set_text(&mut self, text: &str) -> CResult<()> {
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))
+            .and_then(|_| stdout.flush())
+            .map_err(|err| err.to_string())
+    }
+
+    fn get_text(&mut self) -> CResult<String> {
+        Err(String::from("OSC 52 cannot be read back"))
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn name(&self) -> &'static str {
+        "internal register"
+    }
+
+    fn set_text(&mut self, text: &str) -> CResult<()> {
+        self.text = Some(String::from(text));
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> CResult<String> {
+        self.text.clone().ok_or_else(|| String::from("clipboard is empty"))
+    }
+}
+
+// tries every backend in order, the internal register always keeps a copy
+pub struct Clipboard {
+    backends: Vec<Box<dyn ClipboardBackend>>,
+    register: MemoryClipboard,
+    pub init_error: Option<String>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        let mut backends: Vec<Box<dyn ClipboardBackend>> = vec![];
+        let mut init_error = None;
+        match SystemClipboard::new() {
+            Ok(system) => backends.push(Box::new(system)),
+            Err(err) => init_error = Some(err)
+        }
+        if io::stdout().is_terminal() {
+            backends.push(Box::new(Osc52Clipboard));
+        }
+        Clipboard::with_backends(backends, init_error)
+    }
+
+    pub fn with_backends(backends: Vec<Box<dyn ClipboardBackend>>, init_error: Option<String>) -> Self {
+        Clipboard { backends, register: MemoryClipboard::default(), init_error }
+    }
+
+    // returns the name of the backend that took the text
+    pub fn set_text(&mut self, text: &str) -> CResult<&'static str> {
+        self.register.set_text(text)?;
+        let mut errors = vec![];
+        for backend in self.backends.iter_mut() {
+            match backend.set_text(text) {
+                Ok(_) => return Ok(backend.name()),
+                Err(err) => errors.push(format!("{}: {}", backend.name(), err))
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(self.register.name()),
+            false => Err(format!("copy failed ({}), kept in the internal register", errors.join("; ")))
+        }
+    }
+
+    pub fn get_text(&mut self) -> CResult<String> {
+        for backend in self.backends.iter_mut() {
+            if let Ok(text) = backend.get_text() {
+                return Ok(text);
+            }
+        }
+        self.register.get_text()
+    }
+}
+
+// most recent copies first, saved after every change when `file` is set
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ClipHistory {
+    entries: VecDeque<String>,
+    #[serde(skip)]
+    capacity: usize,
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+impl ClipHistory {
+    pub fn new(capacity: usize) -> Self {
+        ClipHistory { entries: VecDeque::new(), capacity, file: None }
+    }
+
+    pub fn load(capacity: usize, file: PathBuf) -> Self {
+        let mut history = crate::state::load::<ClipHistory>(&file).unwrap_or_default();
+        history.capacity = capacity;
+        history.entries.truncate(capacity);
+        history.file = Some(file);
+        history
+    }
+
+    pub fn entries(&self) -> &VecDeque<String> {
+        &self.entries
+    }
+
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() || self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|entry| entry != text);
+        self.entries.push_front(String::from(text));
+        self.entries.truncate(self.capacity);
+        if let Some(file) = &self.file {
+            let _ = crate::state::save(file, self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClipHistory, Clipboard, ClipboardBackend, MemoryClipboard};
+
+    struct BrokenClipboard;
+
+    impl ClipboardBackend for BrokenClipboard {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn set_text(&mut self, _text: &str) -> Result<(), String> {
+            Err(String::from("no display"))
+        }
+
+        fn get_text(&mut self) -> Result<String, String> {
+            Err(String::from("no display"))
+        }
+    }
+
+    #[test]
+    fn memory_round_trip() {
+        let mut clipboard = Clipboard::with_backends(vec![Box::new(MemoryClipboard::default())], None);
+        assert!(clipboard.get_text().is_err());
+        assert_eq!(clipboard.set_text("ls -la"), Ok("internal register"));
+        assert_eq!(clipboard.get_text(), Ok(String::from("ls -la")));
+    }
+
+    #[test]
+    fn falls_back_to_next_backend() {
+        let mut clipboard = Clipboard::with_backends(vec![Box::new(BrokenClipboard), Box::new(MemoryClipboard::default())], None);
+        assert_eq!(clipboard.set_text("echo"), Ok("internal register"));
+    }
+
+    #[test]
+    fn failure_keeps_register() {
+        let mut clipboard = Clipboard::with_backends(vec![Box::new(BrokenClipboard)], None);
+        assert!(clipboard.set_text("echo").is_err());
+        assert_eq!(clipboard.get_text(), Ok(String::from("echo")));
+    }
+
+    #[test]
+    fn history_ring_keeps_latest() {
+        let mut history = ClipHistory::new(2);
+        history.push("a");
+        history.push("b");
+        history.push("a");
+        history.push("c");
+        assert_eq!(history.entries().iter().collect::<Vec<_>>(), vec!["c", "a"]);
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use tui::style::Color;
+
+type EResult<T> = Result<T, Box<dyn Error>>;
+
+pub const CONFIG_DIR: &str = "snippet_rustui";
+pub const CONFIG_FILE: &str = "config.toml";
+// the JSON file older versions kept in the working directory
+pub const LEGACY_ENV_FILE: &str = ".env";
+pub const MIGRATED_ENV_FILE: &str = ".env.snippet-migrated";
+// the top level `repository` is the profile called `default`
+pub const DEFAULT_PROFILE: &str = "default";
+// shows the snippets of every profile at once
+pub const ALL_PROFILES: &str = "all";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub clip_history_size: usize,
+    pub persist_clip_history: bool,
+    pub mouse_capture: bool,
+    pub keymap_file: Option<String>,
+    pub vim_mode: bool,
+    pub autosave_on_leave: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            clip_history_size: 10,
+            persist_clip_history: false,
+            mouse_capture: true,
+            keymap_file: None,
+            vim_mode: false,
+            autosave_on_leave: false,
+        }
+    }
+}
+
+// pulling on start and pushing after every save
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    pub remote: String,
+    pub branch: String,
+}
+
+impl Default for SyncConfig {
+    fn default() -> SyncConfig {
+        SyncConfig { enabled: true, remote: String::from("origin"), branch: String::from("main") }
+    }
+}
+
+// color names like `yellow`, `light-blue` or `#d7af00`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub accent: String,
+    pub muted: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> ThemeConfig {
+        ThemeConfig { accent: String::from("yellow"), muted: String::from("dark-gray") }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Theme {
+    pub accent: Color,
+    pub muted: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme { accent: Color::Yellow, muted: Color::DarkGray }
+    }
+}
+
+impl ThemeConfig {
+    pub fn theme(&self) -> Result<Theme, String> {
+        Ok(Theme { accent: parse_color(&self.accent)?, muted: parse_color(&self.muted)? })
+    }
+}
+
+pub fn parse_color(name: &str) -> Result<Color, String> {
+    let lower = name.trim().to_lowercase().replace(['_', ' '], "-");
+    if let Some(hex) = lower.strip_prefix('#') {
+        let value = match hex.len() {
+            6 => u32::from_str_radix(hex, 16).ok(),
+            _ => None
+        };
+        return match value {
+            Some(value) => Ok(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)),
+            None => Err(format!("invalid color '{}', expected #rrggbb", name))
+        };
+    }
+    match lower.as_str() {
+        "reset" | "default" => Ok(Color::Reset),
+        "black" => Ok(Color::Black),
+        "red" => Ok(Color::Red),
+        "green" => Ok(Color::Green),
+        "yellow" => Ok(Color::Yellow),
+        "blue" => Ok(Color::Blue),
+        "magenta" => Ok(Color::Magenta),
+        "cyan" => Ok(Color::Cyan),
+        "gray" | "grey" => Ok(Color::Gray),
+        "dark-gray" | "dark-grey" => Ok(Color::DarkGray),
+        "light-red" => Ok(Color::LightRed),
+        "light-green" => Ok(Color::LightGreen),
+        "light-yellow" => Ok(Color::LightYellow),
+        "light-blue" => Ok(Color::LightBlue),
+        "light-magenta" => Ok(Color::LightMagenta),
+        "light-cyan" => Ok(Color::LightCyan),
+        "white" => Ok(Color::White),
+        _ => Err(format!("unknown color '{}'", name))
+    }
+}
+
+// another snippet repository, `sync` defaults to the top level one
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    pub repository: String,
+    pub sync: Option<SyncConfig>,
+}
+
+// a snippet repository as used by the app
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    pub name: String,
+    pub path: String,
+    pub sync: SyncConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub repository: Option<String>,
+    // the profile used without `--profile`
+    pub profile: Option<String>,
+    pub settings: Settings,
+    pub sync: SyncConfig,
+    pub theme: ThemeConfig,
+    // `[keymap.<mode>]` tables of `"key" = "action"`, `"none"` unbinds a key
+    pub keymap: BTreeMap<String, BTreeMap<String, String>>,
+    pub profiles: BTreeMap<String, Profile>,
+    // `language = "command"` on top of the built-in interpreters, `"none"` removes one
+    pub runners: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct LegacyEnv {
+    git_folder_path: String,
+    #[serde(default)]
+    settings: Settings,
+}
+
+impl Config {
+    // `$XDG_CONFIG_HOME/snippet_rustui/config.toml` or the platform equivalent
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    pub fn load(path: &Path) -> EResult<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str::<Config>(&text).map_err(|err| format!("{} is not a valid config: {}", path.display(), err).into())
+    }
+
+    pub fn save(&self, path: &Path) -> EResult<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // reads `path`, or moves a `.env` from `legacy_dir` into it on the first run
+    pub fn load_or_migrate(path: &Path, legacy_dir: &Path) -> EResult<(Self, Option<String>)> {
+        if path.exists() {
+            return Ok((Config::load(path)?, None));
+        }
+        let legacy = legacy_dir.join(LEGACY_ENV_FILE);
+        // a dotenv file of some project is not ours to touch
+        let env = match fs::read(&legacy).ok().and_then(|buf| serde_json::from_slice::<LegacyEnv>(&buf).ok()) {
+            Some(env) => env,
+            None => return Ok((Config::default(), None))
+        };
+        let config = Config {
+            repository: Some(String::from(env.git_folder_path.trim())),
+            settings: env.settings,
+            ..Default::default()
+        };
+        config.save(path)?;
+        fs::rename(&legacy, legacy_dir.join(MIGRATED_ENV_FILE))?;
+        let message = format!("Moved the settings of {} to {}", legacy.display(), path.display());
+        Ok((config, Some(message)))
+    }
+
+    // every configured repository, `repository` overrides the top level one
+    pub fn libraries(&self, repository: Option<&str>) -> Result<Vec<Library>, String> {
+        let mut libraries = vec![];
+        if let Some(path) = repository.or(self.repository.as_deref()) {
+            libraries.push(Library { name: String::from(DEFAULT_PROFILE), path: expand_home(path), sync: self.sync.clone() });
+        }
+        for (name, profile) in &self.profiles {
+            if name == DEFAULT_PROFILE || name == ALL_PROFILES {
+                return Err(format!("the profile name '{}' is reserved", name));
+            }
+            libraries.push(Library {
+                name: name.clone(),
+                path: expand_home(&profile.repository),
+                sync: profile.sync.clone().unwrap_or_else(|| self.sync.clone()),
+            });
+        }
+        Ok(libraries)
+    }
+
+    pub fn keymap_overrides(&self) -> HashMap<String, HashMap<String, Option<String>>> {
+        self.keymap.iter()
+            .map(|(scope, bindings)| {
+                let bindings = bindings.iter()
+                    .map(|(key, action)| (key.clone(), match action.as_str() {
+                        "none" => None,
+                        _ => Some(action.clone())
+                    }))
+                    .collect();
+                (scope.clone(), bindings)
+            })
+            .collect()
+    }
+}
+
+// `~/snippets` style paths are relative to the home directory
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).display().to_string(),
+        _ => String::from(path)
+    }
+}
+
+// index of the library called `name`, `None` for all of them
+pub fn find_library(libraries: &[Library], name: &str) -> Result<Option<usize>, String> {
+    if name == ALL_PROFILES {
+        return Ok(None);
+    }
+    match libraries.iter().position(|library| library.name == name) {
+        Some(index) => Ok(Some(index)),
+        None => {
+            let names: Vec<&str> = libraries.iter().map(|library| library.name.as_str()).collect();
+            Err(format!("unknown profile '{}', expected one of {}, {}", name, names.join(", "), ALL_PROFILES))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tui::style::Color;
+    use super::{find_library, parse_color, Config, LEGACY_ENV_FILE, MIGRATED_ENV_FILE};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = Path::new("./target/temp/config").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_partial_config() {
+        let config: Config = toml::from_str(r#"
+            repository = "~/snippets"
+            [settings]
+            vim_mode = true
+            [sync]
+            branch = "master"
+            [keymap.normal]
+            "ctrl-w" = "save"
+            "ctrl-s" = "none"
+        "#).unwrap();
+        assert_eq!(config.repository.as_deref(), Some("~/snippets"));
+        assert!(config.settings.vim_mode);
+        assert_eq!(config.settings.clip_history_size, 10);
+        assert_eq!(config.sync.remote, "origin");
+        assert_eq!(config.sync.branch, "master");
+        let overrides = config.keymap_overrides();
+        assert_eq!(overrides["normal"]["ctrl-w"].as_deref(), Some("save"));
+        assert_eq!(overrides["normal"]["ctrl-s"], None);
+    }
+
+    #[test]
+    fn profiles_become_libraries() {
+        let config: Config = toml::from_str(r#"
+            repository = "/srv/personal"
+            [profiles.team]
+            repository = "/srv/team"
+            sync = { branch = "master" }
+        "#).unwrap();
+        let libraries = config.libraries(None).unwrap();
+        assert_eq!(libraries.len(), 2);
+        assert_eq!(libraries[0].name, "default");
+        assert_eq!(libraries[1].path, "/srv/team");
+        assert_eq!(libraries[1].sync.branch, "master");
+        assert_eq!(libraries[1].sync.remote, "origin");
+        assert_eq!(config.libraries(Some("/tmp/other")).unwrap()[0].path, "/tmp/other");
+        assert_eq!(find_library(&libraries, "team"), Ok(Some(1)));
+        assert_eq!(find_library(&libraries, "all"), Ok(None));
+        assert!(find_library(&libraries, "work").is_err());
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("Light_Blue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color("#d7af00"), Ok(Color::Rgb(0xd7, 0xaf, 0x00)));
+        assert!(parse_color("#d7af").is_err());
+        assert!(parse_color("golden").is_err());
+    }
+
+    #[test]
+    fn migrates_legacy_env() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("config").join("config.toml");
+        fs::write(dir.join(LEGACY_ENV_FILE), r#"{"env_file_name":".env","git_folder_path":"/srv/snippets\n","settings":{"vim_mode":true}}"#).unwrap();
+        let (config, message) = Config::load_or_migrate(&path, &dir).unwrap();
+        assert!(message.is_some());
+        assert_eq!(config.repository.as_deref(), Some("/srv/snippets"));
+        assert!(config.settings.vim_mode);
+        assert!(!dir.join(LEGACY_ENV_FILE).exists());
+        assert!(dir.join(MIGRATED_ENV_FILE).exists());
+        let (config, message) = Config::load_or_migrate(&path, &dir).unwrap();
+        assert!(message.is_none());
+        assert_eq!(config.repository.as_deref(), Some("/srv/snippets"));
+    }
+
+    #[test]
+    fn keeps_foreign_dotenv() {
+        let dir = temp_dir("dotenv");
+        let path = dir.join("config.toml");
+        fs::write(dir.join(LEGACY_ENV_FILE), "DATABASE_URL=postgres://localhost\n").unwrap();
+        let (config, message) = Config::load_or_migrate(&path, &dir).unwrap();
+        assert!(message.is_none());
+        assert!(config.repository.is_none());
+        assert!(dir.join(LEGACY_ENV_FILE).exists());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,104 @@
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use tui::{
+    backend::{Backend},
+    Terminal,
+};
+
+// resolve the editor command line, `$VISUAL` wins over `$EDITOR`
+pub fn editor_command(visual: Option<String>, editor: Option<String>) -> Vec<String> {
+    let command = [visual, editor]
+        .into_iter()
+        .flatten()
+        .find(|cmd| !cmd.trim().is_empty())
+        .unwrap_or_else(|| String::from(if cfg!(windows) { "notepad" } else { "vi" }));
+    command.split_whitespace().map(String::from).collect()
+}
+
+// the editor from `$VISUAL` or `$EDITOR`
+fn user_editor() -> Vec<String> {
+    editor_command(env::var("VISUAL").ok(), env::var("EDITOR").ok())
+}
+
+// open `path` in the user's editor and wait for it
+pub fn edit_file(path: &Path) -> io::Result<()> {
+    run_editor(&user_editor(), path)
+}
+
+fn run_editor(command: &[String], path: &Path) -> io::Result<()> {
+    match Command::new(&command[0]).args(&command[1..]).arg(path).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(io::Error::other(format!("{} exited with {}", command[0], status))),
+        Err(err) => Err(io::Error::new(err.kind(), format!("cannot start {}: {}", command[0], err))),
+    }
+}
+
+// write `text` to a temp file, open it in `command` and read it back
+pub fn edit_text(text: &str, extension: &str, command: &[String]) -> io::Result<String> {
+    // a fresh file with a random name, nobody else can prepare it in the shared temp dir
+    let mut file = tempfile::Builder::new()
+        .prefix("snippet-")
+        .suffix(&format!(".{}", extension))
+        .tempfile()?;
+    file.write_all(text.as_bytes())?;
+    // closed so the editor may replace it, removed when dropped
+    let path = file.into_temp_path();
+    run_editor(command, &path)?;
+    fs::read_to_string(&path)
+}
+
+// leave the TUI like `main` does on exit, run the editor, then restore the TUI
+pub fn edit_with_terminal_suspended<B: Backend>(terminal: &mut Terminal<B>, text: &str, extension: &str, mouse_capture: bool) -> io::Result<String> {
+    suspended(terminal, mouse_capture, || edit_text(text, extension, &user_editor()))
+}
+
+// opens a file as it is on disk, e.g. one that is not a valid snippet
+pub fn edit_file_with_terminal_suspended<B: Backend>(terminal: &mut Terminal<B>, path: &Path, mouse_capture: bool) -> io::Result<()> {
+    suspended(terminal, mouse_capture, || edit_file(path))
+}
+
+fn suspended<B: Backend, T>(terminal: &mut Terminal<B>, mouse_capture: bool, edit: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)?;
+
+    let result = edit();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
+    if mouse_capture {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    terminal.clear()?;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{edit_text, editor_command};
+
+    #[test]
+    fn visual_before_editor() {
+        let command = editor_command(Some("hx".to_string()), Some("nano".to_string()));
+        assert_eq!(command, vec!["hx"]);
+    }
+
+    #[test]
+    fn editor_with_arguments() {
+        let command = editor_command(Some(" ".to_string()), Some("code --wait".to_string()));
+        assert_eq!(command, vec!["code", "--wait"]);
+    }
+
+    #[test]
+    fn edits_a_private_temp_file() {
+        // `true` leaves the text alone
+        assert_eq!(edit_text("echo hi\n", "sh", &[String::from("true")]).unwrap(), "echo hi\n");
+    }
+}
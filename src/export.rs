@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::note::{vscode_id, Note};
+use crate::placeholder::rewrite;
+
+type XResult<T> = Result<T, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// one `<language>.json` per language for VS Code's `snippets` folder
+    Vscode,
+    /// a single Markdown document grouped by language
+    Markdown,
+    /// a static site with `index.html` and its search index
+    Html,
+}
+
+// the snippets grouped by language, both sorted
+fn by_language(notes: &[Note]) -> BTreeMap<String, Vec<&Note>> {
+    let mut groups: BTreeMap<String, Vec<&Note>> = BTreeMap::new();
+    for note in notes {
+        let language = match note.language.trim().is_empty() {
+            true => String::from("text"),
+            false => note.language.trim().to_lowercase()
+        };
+        groups.entry(language).or_default().push(note);
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|note| note.title.to_lowercase());
+    }
+    groups
+}
+
+// the snippets grouped by VS Code's language file, `sh`, `bash` and `zsh` share one
+fn by_vscode_language(notes: &[Note]) -> BTreeMap<String, Vec<&Note>> {
+    let mut groups: BTreeMap<String, Vec<&Note>> = BTreeMap::new();
+    for (language, notes) in by_language(notes) {
+        groups.entry(vscode_language(&language)).or_default().extend(notes);
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|note| note.title.to_lowercase());
+    }
+    groups
+}
+
+fn write(path: &Path, text: &str) -> XResult<PathBuf> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Cannot create {}: {}", parent.display(), err))?;
+    }
+    fs::write(path, text).map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+    Ok(path.to_path_buf())
+}
+
+// writes `notes` below `out`, a folder for VS Code and HTML, a file for Markdown
+pub fn export(notes: &[Note], format: Format, out: &Path) -> XResult<Vec<PathBuf>> {
+    match format {
+        Format::Vscode => by_vscode_language(notes).iter()
+            .map(|(language, notes)| {
+                let json = serde_json::to_string_pretty(&vscode_snippets(notes)).map_err(|err| err.to_string())?;
+                write(&out.join(format!("{}.json", language)), &json)
+            })
+            .collect(),
+        Format::Markdown => Ok(vec![write(out, &markdown(notes))?]),
+        Format::Html => {
+            let index = search_index(notes);
+            let json = serde_json::to_string(&index).map_err(|err| err.to_string())?;
+            Ok(vec![
+                write(&out.join("index.html"), &html(notes, &json))?,
+                write(&out.join("search-index.json"), &json)?,
+            ])
+        }
+    }
+}
+
+// VS Code's id, usable as a file name
+fn vscode_language(language: &str) -> String {
+    vscode_id(language).chars().map(|c| match c.is_alphanumeric() || c == '-' || c == '_' {
+        true => c,
+        false => '-'
+    }).collect()
+}
+
+#[derive(Serialize)]
+struct VscodeSnippet {
+    prefix: Vec<String>,
+    body: Vec<String>,
+    description: String,
+}
+
+// `$` starts a tab stop in VS Code, placeholders become tab stops
+fn vscode_body(contents: &str) -> Vec<String> {
+    let escape = |text: &str, special: &str| -> String {
+        text.chars().fold(String::new(), |mut out, c| {
+            if special.contains(c) {
+                out.push('\\');
+            }
+            out.push(c);
+            out
+        })
+    };
+    let mut stops: Vec<String> = vec![];
+    let body = rewrite(contents, |text| escape(text, "\\$"), |placeholder| {
+        let stop = match stops.iter().position(|name| *name == placeholder.name) {
+            Some(i) => i + 1,
+            None => {
+                stops.push(placeholder.name.clone());
+                stops.len()
+            }
+        };
+        match (&placeholder.default, placeholder.choices.is_empty()) {
+            (_, false) => {
+                let choices: Vec<String> = placeholder.choices.iter().map(|choice| escape(choice, "\\$,|")).collect();
+                format!("${{{}|{}|}}", stop, choices.join(","))
+            }
+            (Some(default), true) => format!("${{{}:{}}}", stop, escape(default, "\\$}")),
+            (None, true) => format!("${{{}:{}}}", stop, placeholder.name)
+        }
+    });
+    body.split('\n').map(String::from).collect()
+}
+
+fn vscode_snippets(notes: &[&Note]) -> BTreeMap<String, VscodeSnippet> {
+    let mut snippets = BTreeMap::new();
+    for note in notes {
+        // the merged view may hold the same title twice
+        let mut name = note.title.clone();
+        let mut count = 1;
+        while snippets.contains_key(&name) {
+            count += 1;
+            name = format!("{} ({})", note.title, count);
+        }
+        let prefix = match note.tags.is_empty() {
+            true => vec![note.title.to_lowercase().split_whitespace().collect::<Vec<_>>().join("-")],
+            false => note.tags.clone()
+        };
+        snippets.insert(name, VscodeSnippet { prefix, body: vscode_body(&note.contents), description: note.title.clone() });
+    }
+    snippets
+}
+
+// a fence longer than any run of backticks in the contents
+fn fence(contents: &str) -> String {
+    let longest = contents.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn markdown(notes: &[Note]) -> String {
+    let groups = by_language(notes);
+    let mut out = String::from("# Snippets\n\n");
+    for (language, notes) in &groups {
+        out.push_str(&format!("- [{}](#{}) ({})\n", language, anchor(language), notes.len()));
+    }
+    for (language, notes) in &groups {
+        out.push_str(&format!("\n## {}\n", language));
+        for note in notes {
+            out.push_str(&format!("\n### {}\n\n", note.title));
+            if !note.tags.is_empty() {
+                let tags: Vec<String> = note.tags.iter().map(|tag| format!("`{}`", tag)).collect();
+                out.push_str(&format!("Tags: {}\n\n", tags.join(", ")));
+            }
+            let fence = fence(&note.contents);
+            out.push_str(&format!("{}{}\n{}\n{}\n", fence, language, note.contents.trim_end_matches('\n'), fence));
+        }
+    }
+    out
+}
+
+// the heading anchor GitHub generates
+fn anchor(heading: &str) -> String {
+    heading.to_lowercase().chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    id: String,
+    title: &'a str,
+    language: &'a str,
+    tags: &'a [String],
+    contents: &'a str,
+}
+
+// what the site searches, `id` is the element of the snippet
+fn search_index(notes: &[Note]) -> Vec<IndexEntry<'_>> {
+    let mut index = vec![];
+    for (_, notes) in by_language(notes) {
+        for note in notes {
+            index.push(IndexEntry {
+                id: format!("s{}", index.len()),
+                title: &note.title,
+                language: &note.language,
+                tags: &note.tags,
+                contents: &note.contents,
+            });
+        }
+    }
+    index
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:0 auto;max-width:60rem;padding:1rem}
+input{width:100%;font-size:1.1rem;padding:.5rem;box-sizing:border-box}
+article{border-bottom:1px solid #ddd;padding:.5rem 0}
+h2{font-size:1.1rem;margin:.3rem 0}
+.language,.tag{font-size:.8rem;color:#666;margin-right:.5rem}
+pre{background:#f5f5f5;padding:.5rem;overflow-x:auto}
+button{float:right}";
+
+// every search word has to appear in the title, language, tags or contents
+const SCRIPT: &str = "const index = JSON.parse(document.getElementById('index').textContent);
+const search = document.getElementById('search');
+const count = document.getElementById('count');
+function filter() {
+  const words = search.value.toLowerCase().split(/\\s+/).filter(w => w);
+  let shown = 0;
+  for (const entry of index) {
+    const text = [entry.title, entry.language, entry.tags.join(' '), entry.contents].join(' ').toLowerCase();
+    const match = words.every(w => text.includes(w));
+    document.getElementById(entry.id).hidden = !match;
+    if (match) shown++;
+  }
+  count.textContent = shown + ' of ' + index.length + ' snippets';
+}
+search.addEventListener('input', filter);
+document.querySelectorAll('button[data-copy]').forEach(button => button.addEventListener('click', () => {
+  const entry = index.find(e => e.id === button.dataset.copy);
+  navigator.clipboard.writeText(entry.contents);
+}));
+filter();";
+
+// one page with every snippet, the index is embedded so it works from the file system
+fn html(notes: &[Note], index: &str) -> String {
+    let mut articles = String::new();
+    let mut id = 0;
+    for (_, notes) in by_language(notes) {
+        for note in notes {
+            let tags: String = note.tags.iter().map(|tag| format!("<span class=\"tag\">#{}</span>", escape_html(tag))).collect();
+            articles.push_str(&format!(
+                "<article id=\"s{id}\"><button data-copy=\"s{id}\">Copy</button><h2>{}</h2><span class=\"language\">{}</span>{}<pre><code>{}</code></pre></article>\n",
+                escape_html(&note.title), escape_html(&note.language), tags, escape_html(&note.contents)
+            ));
+            id += 1;
+        }
+    }
+    // `</script>` inside a snippet must not end the embedded index
+    let index = index.replace("</", "<\\/");
+    format!("<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>Snippets</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<h1>Snippets</h1>
+<input id=\"search\" type=\"search\" placeholder=\"Search titles, languages, tags and contents\" autofocus>
+<p id=\"count\"></p>
+{articles}<script type=\"application/json\" id=\"index\">{index}</script>
+<script>{SCRIPT}</script>
+</body>
+</html>
+")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{export, html, markdown, search_index, vscode_body, vscode_language, Format};
+    use crate::note::Note;
+    use std::path::Path;
+
+    #[test]
+    fn vscode_tab_stops() {
+        assert_eq!(vscode_body("echo $HOME {{name}}\n{{env|dev,prod}} {{port:80}} {{name}}"), vec![
+            "echo \\$HOME ${1:name}",
+            "${2|dev,prod|} ${3:80} ${1:name}",
+        ]);
+        assert_eq!(vscode_language("sh"), "shellscript");
+        assert_eq!(vscode_language("c#"), "csharp");
+    }
+
+    #[test]
+    fn shells_share_one_vscode_file() {
+        let out = Path::new("./target/temp_export_vscode");
+        let _ = std::fs::remove_dir_all(out);
+        let notes = vec![Note::new("repo", "sh", "list", "ls"), Note::new("repo", "bash", "disk", "df -h")];
+        let files = export(&notes, Format::Vscode, out).unwrap();
+        assert_eq!(files, vec![out.join("shellscript.json")]);
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&files[0]).unwrap()).unwrap();
+        assert!(json.get("list").is_some() && json.get("disk").is_some());
+    }
+
+    #[test]
+    fn markdown_groups_by_language() {
+        let mut hello = Note::new("repo", "python", "hello", "print('```')\n");
+        hello.tags = vec![String::from("demo")];
+        let notes = vec![Note::new("repo", "sh", "list", "ls"), hello];
+        let text = markdown(&notes);
+        assert!(text.starts_with("# Snippets\n\n- [python](#python) (1)\n- [sh](#sh) (1)\n"));
+        assert!(text.contains("## python\n\n### hello\n\nTags: `demo`\n\n````python\nprint('```')\n````\n"));
+        assert!(text.find("## python").unwrap() < text.find("## sh").unwrap());
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        let notes = vec![Note::new("repo", "html", "tag", "<script>alert(1)</script>")];
+        let index = serde_json::to_string(&search_index(&notes)).unwrap();
+        let page = html(&notes, &index);
+        assert!(page.contains("<code>&lt;script&gt;alert(1)&lt;/script&gt;</code>"));
+        assert!(page.contains("alert(1)<\\/script>"));
+        assert_eq!(page.matches("</script>").count(), 2);
+    }
+}
@@ -21,24 +21,73 @@ pub fn git_commit(path: &str, message: &str) {
             .expect("git commit failed");
 }
 
-pub fn git_push(path: &str) {
+pub fn git_push(path: &str, remote: &str, branch: &str) {
     Command::new("git")      
             .arg("-C")
             .arg(path)
             .arg("push")
-            .arg("origin")
-            .arg("main")
+            .arg(remote)
+            .arg(branch)
             .output()
             .expect("git commit failed");
 }
 
-pub fn git_pull(path: &str) {
+pub fn git_pull(path: &str, remote: &str, branch: &str) {
     Command::new("git")      
             .arg("-C")
             .arg(path)
             .arg("pull")
-            .arg("origin")
-            .arg("main")
+            .arg(remote)
+            .arg(branch)
             .output()
             .expect("git commit failed");
 }
+
+// runs git in `path`, returning its output or what it printed on failure
+fn run_git(path: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(args)
+            .output()
+            .map_err(|err| format!("cannot run git: {}", err))?;
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+pub fn git_init(path: &str, branch: &str) -> Result<(), String> {
+    run_git(path, &["init", "-b", branch]).map(|_| ())
+}
+
+pub fn git_commit_all(path: &str, message: &str) -> Result<(), String> {
+    run_git(path, &["add", "."])?;
+    run_git(path, &["commit", "-m", message]).map(|_| ())
+}
+
+pub fn git_add_remote(path: &str, name: &str, url: &str) -> Result<(), String> {
+    run_git(path, &["remote", "add", name, url]).map(|_| ())
+}
+
+pub fn git_remote_url(path: &str, name: &str) -> Option<String> {
+    run_git(path, &["remote", "get-url", name]).ok()
+}
+
+// also works before the first commit
+pub fn git_current_branch(path: &str) -> Option<String> {
+    run_git(path, &["symbolic-ref", "--short", "HEAD"]).ok()
+}
+
+// commits everything that changed, a clean tree is fine
+pub fn git_commit_changes(path: &str, message: &str) -> Result<(), String> {
+    match run_git(path, &["status", "--porcelain"])?.is_empty() {
+        true => Ok(()),
+        false => git_commit_all(path, message)
+    }
+}
+
+pub fn git_sync(path: &str, remote: &str, branch: &str) -> Result<(), String> {
+    run_git(path, &["pull", remote, branch])?;
+    run_git(path, &["push", remote, branch]).map(|_| ())
+}
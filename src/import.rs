@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use clap::ValueEnum;
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::note::{check_name, from_vscode_id, language_name, Note};
+
+type IResult<T> = Result<T, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// VS Code `*.code-snippets` or `snippets/<language>.json`
+    Vscode,
+    /// pet's `snippet.toml`
+    Pet,
+    /// a gist clone or any folder of source files, one snippet per file
+    Gist,
+}
+
+// a snippet read from another tool, not yet saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub language: String,
+    pub title: String,
+    pub contents: String,
+    pub tags: Vec<String>,
+    // the file it came from, for the summary
+    pub source: String,
+}
+
+// what the import does with an entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    New,
+    // the same contents exist as this `language/title`
+    Duplicate(String),
+    // the title is used for other contents
+    Taken,
+    // the title or language would leave the library
+    Invalid(String),
+}
+
+// VS Code files by their extension, pet by its TOML, anything else is a gist
+pub fn detect(path: &Path) -> Format {
+    let extension = |path: &Path| path.extension().and_then(OsStr::to_str).map(str::to_lowercase);
+    let is_vscode = |path: &Path| matches!(extension(path).as_deref(), Some("code-snippets") | Some("json"));
+    if path.is_file() {
+        return match extension(path).as_deref() {
+            Some("toml") => Format::Pet,
+            _ if is_vscode(path) => Format::Vscode,
+            _ => Format::Gist
+        };
+    }
+    let files: Vec<_> = source_files(path).collect();
+    match !files.is_empty() && files.iter().all(|file| is_vscode(file)) {
+        true => Format::Vscode,
+        false => Format::Gist
+    }
+}
+
+// the files below `path`, leaving out hidden ones like `.git`
+fn source_files(path: &Path) -> impl Iterator<Item = std::path::PathBuf> {
+    WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+}
+
+// the entries below `path` and the files of a folder that could not be read, those are skipped
+pub fn read(path: &Path, format: Format) -> IResult<(Vec<Entry>, Vec<String>)> {
+    if path.is_file() {
+        return read_file(path, format).map(|entries| (entries, vec![]));
+    }
+    let (mut entries, mut skipped) = (vec![], vec![]);
+    for file in source_files(path) {
+        match read_file(&file, format) {
+            Ok(mut read) => entries.append(&mut read),
+            Err(err) => skipped.push(err)
+        }
+    }
+    Ok((entries, skipped))
+}
+
+fn read_file(file: &Path, format: Format) -> IResult<Vec<Entry>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        // binary files of a gist
+        Err(_) if format == Format::Gist => return Ok(vec![]),
+        Err(err) => return Err(format!("Cannot read {}: {}", file.display(), err))
+    };
+    let source = file.display().to_string();
+    let mut read = match format {
+        Format::Vscode => read_vscode(file, &text),
+        Format::Pet => read_pet(&text),
+        Format::Gist => Ok(read_gist(file, &text).into_iter().collect())
+    }.map_err(|err| format!("{}: {}", source, err))?;
+    for entry in read.iter_mut() {
+        entry.source = source.clone();
+    }
+    Ok(read)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Body {
+    Line(String),
+    Lines(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct VscodeSnippet {
+    body: Body,
+    #[serde(default)]
+    prefix: Option<Body>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+fn read_vscode(file: &Path, text: &str) -> IResult<Vec<Entry>> {
+    let snippets: BTreeMap<String, VscodeSnippet> = serde_json::from_str(&strip_jsonc(text)).map_err(|err| err.to_string())?;
+    // `snippets/python.json` holds python snippets, `*.code-snippets` name theirs in `scope`
+    let file_language = match file.extension().and_then(OsStr::to_str) {
+        Some("json") => file.file_stem().map(|stem| from_vscode_id(&stem.to_string_lossy())),
+        _ => None
+    };
+    Ok(snippets.into_iter().map(|(name, snippet)| {
+        let language = file_language.clone().unwrap_or_else(|| {
+            from_vscode_id(snippet.scope.as_deref().unwrap_or("").split(',').next().unwrap_or(""))
+        });
+        let tags = match snippet.prefix {
+            Some(Body::Line(prefix)) => vec![prefix],
+            Some(Body::Lines(prefixes)) => prefixes,
+            None => vec![]
+        };
+        let contents = match snippet.body {
+            Body::Line(line) => line,
+            Body::Lines(lines) => lines.join("\n")
+        };
+        Entry { language, title: name, contents, tags, source: String::new() }
+    }).collect())
+}
+
+#[derive(Deserialize)]
+struct PetFile {
+    #[serde(default)]
+    snippets: Vec<PetSnippet>,
+}
+
+#[derive(Deserialize)]
+struct PetSnippet {
+    #[serde(default)]
+    description: String,
+    command: String,
+    #[serde(default)]
+    tag: Vec<String>,
+}
+
+// pet only keeps shell commands
+fn read_pet(text: &str) -> IResult<Vec<Entry>> {
+    let file: PetFile = toml::from_str(text).map_err(|err| err.to_string())?;
+    Ok(file.snippets.into_iter().map(|snippet| Entry {
+        language: String::from("sh"),
+        title: match snippet.description.trim().is_empty() {
+            true => snippet.command.lines().next().unwrap_or("").to_string(),
+            false => snippet.description
+        },
+        contents: snippet.command,
+        tags: snippet.tag,
+        source: String::new(),
+    }).collect())
+}
+
+fn read_gist(file: &Path, text: &str) -> Option<Entry> {
+    let title = file.file_stem()?.to_string_lossy().into_owned();
+    let extension = file.extension().and_then(OsStr::to_str).unwrap_or("").to_lowercase();
+    Some(Entry { language: language_name(&extension), title, contents: String::from(text), tags: vec![], source: String::new() })
+}
+
+// calls `keep` for every char outside of strings, chars inside strings are kept as they are
+fn outside_strings(text: &str, mut keep: impl FnMut(char, &mut Peekable<Chars>) -> Option<char>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        in_string = c == '"';
+        out.extend(keep(c, &mut chars));
+    }
+    out
+}
+
+// VS Code snippet files may have comments and trailing commas
+fn strip_jsonc(text: &str) -> String {
+    let code = outside_strings(text, |c, chars| match (c, chars.peek()) {
+        ('/', Some('/')) => {
+            while chars.peek().is_some_and(|c| *c != '\n') {
+                chars.next();
+            }
+            None
+        }
+        ('/', Some('*')) => {
+            chars.next();
+            let mut last = ' ';
+            for c in chars.by_ref() {
+                if last == '*' && c == '/' {
+                    break;
+                }
+                last = c;
+            }
+            None
+        }
+        _ => Some(c)
+    });
+    outside_strings(&code, |c, chars| match c {
+        ',' if matches!(chars.clone().find(|c| !c.is_whitespace()), Some('}') | Some(']')) => None,
+        _ => Some(c)
+    })
+}
+
+// titles become file names
+fn clean_title(title: &str) -> String {
+    title.trim().chars().map(|c| match c {
+        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+        c if c.is_control() => ' ',
+        c => c
+    }).collect()
+}
+
+// decides what happens to each entry, comparing it to `existing` and to the entries before it
+pub fn plan(entries: Vec<Entry>, existing: &[Note]) -> Vec<(Entry, Outcome)> {
+    let key = |language: &str, title: &str| (language.to_lowercase(), title.to_lowercase());
+    let mut titles: BTreeMap<(String, String), String> = existing.iter()
+        .map(|note| (key(&note.language, &note.title), note.contents.clone()))
+        .collect();
+    // the same contents in another language are another snippet
+    let same = |language: &str, contents: &str| (language.to_lowercase(), String::from(contents.trim()));
+    let mut contents: BTreeMap<(String, String), String> = existing.iter()
+        .map(|note| (same(&note.language, &note.contents), format!("{}/{}", note.language, note.title)))
+        .collect();
+    entries.into_iter()
+        .filter_map(|mut entry| {
+            entry.title = clean_title(&entry.title);
+            entry.language = clean_title(&entry.language);
+            if entry.title.is_empty() || entry.contents.trim().is_empty() {
+                return None;
+            }
+            if let Err(err) = check_name("title", &entry.title).and_then(|_| check_name("language", &entry.language)) {
+                return Some((entry, Outcome::Invalid(err)));
+            }
+            let outcome = match (contents.get(&same(&entry.language, &entry.contents)), titles.contains_key(&key(&entry.language, &entry.title))) {
+                (Some(same), _) => Outcome::Duplicate(same.clone()),
+                (None, true) => Outcome::Taken,
+                (None, false) => {
+                    titles.insert(key(&entry.language, &entry.title), entry.contents.clone());
+                    contents.insert(same(&entry.language, &entry.contents), format!("{}/{}", entry.language, entry.title));
+                    Outcome::New
+                }
+            };
+            Some((entry, outcome))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use super::{plan, read_gist, read_pet, read_vscode, strip_jsonc, Entry, Outcome};
+    use crate::note::Note;
+
+    #[test]
+    fn reads_vscode_snippets() {
+        let text = r#"{
+            // a comment with "quotes"
+            "Print to console": {
+                "scope": "javascript,typescript",
+                "prefix": "log",
+                "body": ["console.log('$1');", "$2"], /* block */
+            },
+            "url": { "prefix": ["u"], "body": "see http://example.com", },
+        }"#;
+        assert!(strip_jsonc(text).contains("http://example.com"));
+        let entries = read_vscode(Path::new("my.code-snippets"), text).unwrap();
+        assert_eq!(entries[0].title, "Print to console");
+        assert_eq!(entries[0].language, "javascript");
+        assert_eq!(entries[0].contents, "console.log('$1');\n$2");
+        assert_eq!(entries[0].tags, vec!["log"]);
+        assert_eq!(entries[1].language, "text");
+        let entries = read_vscode(Path::new("snippets/shellscript.json"), text).unwrap();
+        assert_eq!(entries[1].language, "sh");
+    }
+
+    #[test]
+    fn reads_pet_and_gists() {
+        let text = "[[snippets]]\ndescription = \"list pods\"\ncommand = \"kubectl get pods\"\ntag = [\"k8s\"]\noutput = \"\"\n";
+        let entries = read_pet(text).unwrap();
+        assert_eq!((entries[0].title.as_str(), entries[0].language.as_str(), entries[0].contents.as_str()), ("list pods", "sh", "kubectl get pods"));
+        assert_eq!(entries[0].tags, vec!["k8s"]);
+        let entry = read_gist(Path::new("gist/hello.py"), "print(1)").unwrap();
+        assert_eq!((entry.title.as_str(), entry.language.as_str()), ("hello", "python"));
+    }
+
+    #[test]
+    fn detects_duplicates() {
+        let entry = |title: &str, contents: &str| Entry {
+            language: String::from("sh"),
+            title: String::from(title),
+            contents: String::from(contents),
+            tags: vec![],
+            source: String::new(),
+        };
+        let existing = vec![Note::new("repo", "sh", "list", "ls -la")];
+        let mut other = entry("say", "echo b");
+        other.language = String::from("text");
+        let planned = plan(vec![
+            entry("ls", "ls -la\n"),
+            entry("List", "ls"),
+            entry("a/b", "echo b"),
+            entry("again", "echo b"),
+            entry("empty", " "),
+            entry("..", "echo up"),
+            other,
+        ], &existing);
+        let outcomes: Vec<(&str, &Outcome)> = planned.iter().map(|(entry, outcome)| (entry.title.as_str(), outcome)).collect();
+        assert_eq!(outcomes, vec![
+            ("ls", &Outcome::Duplicate(String::from("sh/list"))),
+            ("List", &Outcome::Taken),
+            ("a-b", &Outcome::New),
+            ("again", &Outcome::Duplicate(String::from("sh/a-b"))),
+            ("..", &Outcome::Invalid(String::from("The title '..' must not contain '/' or '\\' or be '..'"))),
+            ("say", &Outcome::New),
+        ]);
+    }
+}
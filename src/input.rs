@@ -0,0 +1,216 @@
+// cursor and selection of the focused text field, positions are char indices
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextCursor {
+    pub position: usize,
+    pub anchor: Option<usize>,
+}
+
+impl Default for TextCursor {
+    fn default() -> TextCursor {
+        TextCursor::end()
+    }
+}
+
+fn byte_index(text: &str, index: usize) -> usize {
+    text.char_indices().nth(index).map(|(i, _)| i).unwrap_or(text.len())
+}
+
+fn char_count(text: &str) -> usize {
+    text.chars().count()
+}
+
+impl TextCursor {
+    // cursor placed behind the last char of whatever field it is used on
+    pub fn end() -> TextCursor {
+        TextCursor { position: usize::MAX, anchor: None }
+    }
+
+    pub fn clamp(&mut self, text: &str) {
+        let len = char_count(text);
+        self.position = self.position.min(len);
+        self.anchor = self.anchor.map(|a| a.min(len));
+    }
+
+    // ordered, non-empty selection range
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.anchor {
+            Some(anchor) if anchor != self.position => Some((anchor.min(self.position), anchor.max(self.position))),
+            _ => None
+        }
+    }
+
+    pub fn selected_text(&self, text: &str) -> Option<String> {
+        self.selection().map(|(start, end)| text.chars().skip(start).take(end - start).collect())
+    }
+
+    pub fn select_all(&mut self, text: &str) {
+        self.anchor = Some(0);
+        self.position = char_count(text);
+    }
+
+    fn delete_selection(&mut self, buffer: &mut String) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                buffer.replace_range(byte_index(buffer, start)..byte_index(buffer, end), "");
+                self.position = start;
+                self.anchor = None;
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    pub fn insert(&mut self, buffer: &mut String, s: &str) {
+        self.clamp(buffer);
+        self.delete_selection(buffer);
+        buffer.insert_str(byte_index(buffer, self.position), s);
+        self.position += char_count(s);
+    }
+
+    pub fn cut(&mut self, buffer: &mut String) -> Option<String> {
+        self.clamp(buffer);
+        let text = self.selected_text(buffer);
+        self.delete_selection(buffer);
+        text
+    }
+
+    pub fn backspace(&mut self, buffer: &mut String) {
+        self.clamp(buffer);
+        if !self.delete_selection(buffer) && self.position > 0 {
+            self.position -= 1;
+            buffer.remove(byte_index(buffer, self.position));
+        }
+    }
+
+    pub fn delete(&mut self, buffer: &mut String) {
+        self.clamp(buffer);
+        if !self.delete_selection(buffer) && self.position < char_count(buffer) {
+            buffer.remove(byte_index(buffer, self.position));
+        }
+    }
+
+    pub fn clear(&mut self, buffer: &mut String) {
+        buffer.clear();
+        *self = TextCursor { position: 0, anchor: None };
+    }
+
+    // start or drop the selection before a movement
+    fn prepare_move(&mut self, text: &str, select: bool) {
+        self.clamp(text);
+        match select {
+            true => if self.anchor.is_none() { self.anchor = Some(self.position) },
+            false => self.anchor = None
+        }
+    }
+
+    pub fn move_to(&mut self, text: &str, position: usize, select: bool) {
+        self.prepare_move(text, select);
+        self.position = position.min(char_count(text));
+    }
+
+    pub fn move_left(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        self.position = self.position.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        self.position = (self.position + 1).min(char_count(text));
+    }
+
+    pub fn move_home(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        let (line, _) = self.line_col(text);
+        self.position = TextCursor::index_of(text, line, 0);
+    }
+
+    pub fn move_end(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        let (line, _) = self.line_col(text);
+        self.position = TextCursor::index_of(text, line, usize::MAX);
+    }
+
+    pub fn move_up(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        let (line, col) = self.line_col(text);
+        if line > 0 {
+            self.position = TextCursor::index_of(text, line - 1, col);
+        }
+    }
+
+    pub fn move_down(&mut self, text: &str, select: bool) {
+        self.prepare_move(text, select);
+        let (line, col) = self.line_col(text);
+        if line + 1 < text.split('\n').count() {
+            self.position = TextCursor::index_of(text, line + 1, col);
+        }
+    }
+
+    // zero based line and char column of the cursor
+    pub fn line_col(&self, text: &str) -> (usize, usize) {
+        let before: Vec<char> = text.chars().take(self.position).collect();
+        let line = before.iter().filter(|c| **c == '\n').count();
+        let col = before.iter().rev().take_while(|c| **c != '\n').count();
+        (line, col)
+    }
+
+    // char index of `line`/`col`, clamped to the existing text
+    pub fn index_of(text: &str, line: usize, col: usize) -> usize {
+        let mut index = 0;
+        for (i, l) in text.split('\n').enumerate() {
+            let len = char_count(l);
+            if i == line {
+                return index + col.min(len);
+            }
+            index += len + 1;
+        }
+        char_count(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TextCursor;
+
+    #[test]
+    fn insert_at_cursor() {
+        let mut buffer = String::from("hello world");
+        let mut cursor = TextCursor { position: 5, anchor: None };
+        cursor.insert(&mut buffer, ",");
+        assert_eq!(buffer, "hello, world");
+        assert_eq!(cursor.position, 6);
+    }
+
+    #[test]
+    fn insert_replaces_selection() {
+        let mut buffer = String::from("hello world");
+        let mut cursor = TextCursor { position: 11, anchor: Some(6) };
+        assert_eq!(cursor.selected_text(&buffer), Some(String::from("world")));
+        cursor.insert(&mut buffer, "rust");
+        assert_eq!(buffer, "hello rust");
+        assert_eq!(cursor.selection(), None);
+    }
+
+    #[test]
+    fn shift_movement_selects() {
+        let buffer = String::from("ab\ncd");
+        let mut cursor = TextCursor::end();
+        cursor.move_left(&buffer, true);
+        cursor.move_up(&buffer, true);
+        assert_eq!(cursor.selected_text(&buffer), Some(String::from("b\ncd")));
+        cursor.move_right(&buffer, false);
+        assert_eq!(cursor.selection(), None);
+    }
+
+    #[test]
+    fn backspace_multibyte() {
+        let mut buffer = String::from("日本語");
+        let mut cursor = TextCursor { position: 2, anchor: None };
+        cursor.backspace(&mut buffer);
+        assert_eq!(buffer, "日語");
+        assert_eq!(cursor.line_col(&buffer), (0, 1));
+    }
+}
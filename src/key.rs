@@ -1,6 +1,7 @@
 use std::fmt;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum InputMode {
     Normal,
     EditingSearch,
@@ -15,7 +16,6 @@ impl fmt::Display for InputMode {
     }
 }
 
-#[allow(dead_code)]
 impl InputMode {
     pub fn next_mode(&self) -> InputMode {
         match self {
@@ -37,9 +37,199 @@ impl InputMode {
     }
 }
 
+macro_rules! actions {
+    ($($variant:ident => $name:literal, $description:literal;)*) => {
+        // everything a key can be bound to
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Action {
+            $($variant),*
+        }
+
+        #[allow(dead_code)]
+        impl Action {
+            pub const ALL: &'static [Action] = &[$(Action::$variant),*];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Action::$variant => $name),*
+                }
+            }
+
+            pub fn description(&self) -> &'static str {
+                match self {
+                    $(Action::$variant => $description),*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    Quit => "quit", "Quit the app";
+    ForceQuit => "force_quit", "Quit without saving changes";
+    NewNote => "new_note", "Create a new snippet";
+    DeleteNote => "delete_note", "Delete the selected snippet";
+    Save => "save", "Save the snippet and sync all";
+    OpenEditor => "open_editor", "Edit the contents in $VISUAL/$EDITOR";
+    ClipHistory => "clip_history", "Pick from the clipboard history";
+    SwitchLibrary => "switch_library", "Switch to another snippet library";
+    SelectNext => "select_next", "Select the next snippet";
+    SelectPrevious => "select_previous", "Select the previous snippet";
+    Unselect => "unselect", "Clear the list selection";
+    SelectFirst => "select_first", "Select the first snippet";
+    SelectLast => "select_last", "Select the last snippet";
+    NextField => "next_field", "Focus the next pane";
+    PreviousField => "previous_field", "Focus the previous pane";
+    FocusList => "focus_list", "Return to normal mode";
+    FocusTitle => "focus_title", "Edit the title";
+    FocusSearch => "focus_search", "Jump to the search bar";
+    FocusLanguage => "focus_language", "Edit the language";
+    FocusContents => "focus_contents", "Edit the contents";
+    EditContents => "edit_contents", "Insert at the start of the contents";
+    AppendContents => "append_contents", "Append to the end of the contents";
+    OpenLine => "open_line", "Open a new line below the contents";
+    CommandLine => "command_line", "Enter a :command";
+    CommandPalette => "command_palette", "Search and run actions";
+    Help => "help", "Show the keys of the current mode";
+    ApplySearch => "apply_search", "Filter the list by the search text";
+    Run => "run", "Run the snippet with the interpreter of its language";
+    ShowProblems => "show_problems", "Show the files that could not be loaded";
+    Copy => "copy", "Copy the selection, field or snippet";
+    Cut => "cut", "Cut the selection";
+    Paste => "paste", "Paste at the cursor";
+    SelectAll => "select_all", "Select the whole field";
+    ClearField => "clear_field", "Clear the field";
+    InsertNewline => "insert_newline", "Insert a line break";
+    DeleteBackward => "delete_backward", "Delete the char before the cursor";
+    DeleteForward => "delete_forward", "Delete the char after the cursor";
+    CursorLeft => "cursor_left", "Move the cursor left";
+    CursorRight => "cursor_right", "Move the cursor right";
+    CursorUp => "cursor_up", "Move the cursor up";
+    CursorDown => "cursor_down", "Move the cursor down";
+    CursorHome => "cursor_home", "Move the cursor to the line start";
+    CursorEnd => "cursor_end", "Move the cursor to the line end";
+    SelectLeft => "select_left", "Extend the selection left";
+    SelectRight => "select_right", "Extend the selection right";
+    SelectUp => "select_up", "Extend the selection up";
+    SelectDown => "select_down", "Extend the selection down";
+    SelectHome => "select_home", "Extend the selection to the line start";
+    SelectEnd => "select_end", "Extend the selection to the line end";
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+
+    // actions working on the focused text field
+    pub fn is_editing(&self) -> bool {
+        matches!(self,
+            Action::Cut | Action::Paste | Action::SelectAll | Action::ClearField | Action::InsertNewline
+            | Action::DeleteBackward | Action::DeleteForward
+            | Action::CursorLeft | Action::CursorRight | Action::CursorUp | Action::CursorDown | Action::CursorHome | Action::CursorEnd
+            | Action::SelectLeft | Action::SelectRight | Action::SelectUp | Action::SelectDown | Action::SelectHome | Action::SelectEnd)
+    }
+
+    // actions of the vim keymap, nothing runs them without `vim_mode`
+    pub fn is_vim(&self) -> bool {
+        matches!(self,
+            Action::CommandLine | Action::EditContents | Action::AppendContents | Action::OpenLine
+            | Action::SelectFirst | Action::SelectLast)
+    }
+}
+
+// a key with its modifiers, written like `ctrl-s`, `shift-left` or `f1`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // chars carry their own case and shift-tab arrives as backtab
+        let mut modifiers = modifiers;
+        if let KeyCode::Char(_) | KeyCode::BackTab = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        KeyChord { code, modifiers }
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        KeyChord::new(key.code, key.modifiers)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            let lower = rest.to_lowercase();
+            let (modifier, len) = match () {
+                _ if lower.starts_with("ctrl-") => (KeyModifiers::CONTROL, 5),
+                _ if lower.starts_with("alt-") => (KeyModifiers::ALT, 4),
+                _ if lower.starts_with("shift-") => (KeyModifiers::SHIFT, 6),
+                _ => break
+            };
+            modifiers.insert(modifier);
+            rest = &rest[len..];
+        }
+        let code = match rest.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            "minus" => KeyCode::Char('-'),
+            f if f.len() > 1 && f.starts_with('f') && f[1..].parse::<u8>().is_ok() => KeyCode::F(f[1..].parse().unwrap()),
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key '{}'", text))
+                }
+            }
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [(KeyModifiers::CONTROL, "ctrl-"), (KeyModifiers::ALT, "alt-"), (KeyModifiers::SHIFT, "shift-")] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::BackTab => write!(f, "shift-tab"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            code => write!(f, "{}", format!("{:?}", code).to_lowercase())
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::InputMode;
+    use super::{Action, InputMode, KeyChord};
+    use crossterm::event::{KeyCode, KeyModifiers};
 
     #[test]
     fn normal_to_edit_search(){
@@ -77,5 +267,27 @@ mod test {
         assert_eq!(input.previous_mode(), InputMode::EditingCode);
     }
 
+    #[test]
+    fn parse_key_chords(){
+        assert_eq!(KeyChord::parse("ctrl-s"), Ok(KeyChord::new(KeyCode::Char('s'), KeyModifiers::CONTROL)));
+        assert_eq!(KeyChord::parse("shift-tab"), Ok(KeyChord::new(KeyCode::BackTab, KeyModifiers::NONE)));
+        assert_eq!(KeyChord::parse("Alt-Shift-Left"), Ok(KeyChord::new(KeyCode::Left, KeyModifiers::ALT | KeyModifiers::SHIFT)));
+        assert_eq!(KeyChord::parse("f1"), Ok(KeyChord::new(KeyCode::F(1), KeyModifiers::NONE)));
+        assert!(KeyChord::parse("ctrl-").is_err());
+        assert!(KeyChord::parse("hyper-x").is_err());
+    }
+
+    #[test]
+    fn key_chord_round_trip(){
+        for text in ["ctrl-s", "shift-tab", "alt-1", "enter", "shift-left", "G", "f12"] {
+            assert_eq!(KeyChord::parse(text).unwrap().to_string(), text);
+        }
+    }
 
+    #[test]
+    fn action_names(){
+        for action in Action::ALL {
+            assert_eq!(Action::from_name(action.name()), Some(*action));
+        }
+    }
 }
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fs;
+use crossterm::event::KeyEvent;
+
+use crate::key::{Action, InputMode, KeyChord};
+
+// where a binding applies, looked up from the most specific scope outwards
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Scope {
+    Global,
+    Editing,
+    Mode(InputMode),
+}
+
+impl Scope {
+    pub fn from_name(name: &str) -> Option<Scope> {
+        match name {
+            "global" => Some(Scope::Global),
+            "editing" => Some(Scope::Editing),
+            "normal" => Some(Scope::Mode(InputMode::Normal)),
+            "search" => Some(Scope::Mode(InputMode::EditingSearch)),
+            "title" => Some(Scope::Mode(InputMode::EditingTitle)),
+            "language" => Some(Scope::Mode(InputMode::EditingLanguage)),
+            "code" => Some(Scope::Mode(InputMode::EditingCode)),
+            _ => None
+        }
+    }
+
+    fn lookup_order(mode: InputMode) -> Vec<Scope> {
+        match mode {
+            InputMode::Normal => vec![Scope::Mode(mode), Scope::Global],
+            _ => vec![Scope::Mode(mode), Scope::Editing, Scope::Global],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    scope: Scope,
+    chord: KeyChord,
+    // `None` removes a default binding
+    action: Option<Action>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+const DEFAULT_BINDINGS: &[(&str, &str, Action)] = &[
+    ("global", "ctrl-p", Action::CommandPalette),
+    ("global", "f1", Action::Help),
+    ("global", "alt-1", Action::FocusSearch),
+    ("global", "alt-2", Action::FocusList),
+    ("global", "alt-3", Action::FocusTitle),
+    ("global", "alt-4", Action::FocusLanguage),
+    ("global", "alt-5", Action::FocusContents),
+    ("normal", "?", Action::Help),
+    ("normal", "enter", Action::FocusTitle),
+    ("normal", "tab", Action::NextField),
+    ("normal", "shift-tab", Action::PreviousField),
+    ("normal", "ctrl-q", Action::Quit),
+    ("normal", "ctrl-n", Action::NewNote),
+    ("normal", "ctrl-d", Action::DeleteNote),
+    ("normal", "ctrl-y", Action::ClipHistory),
+    ("normal", "ctrl-e", Action::OpenEditor),
+    ("normal", "ctrl-o", Action::SwitchLibrary),
+    ("normal", "ctrl-c", Action::Copy),
+    ("normal", "ctrl-r", Action::Run),
+    ("normal", "f8", Action::ShowProblems),
+    ("normal", "ctrl-s", Action::Save),
+    ("normal", "left", Action::Unselect),
+    ("normal", "down", Action::SelectNext),
+    ("normal", "up", Action::SelectPrevious),
+    ("editing", "ctrl-u", Action::ClearField),
+    ("editing", "tab", Action::NextField),
+    ("editing", "shift-tab", Action::PreviousField),
+    ("editing", "esc", Action::FocusList),
+    ("editing", "ctrl-a", Action::SelectAll),
+    ("editing", "ctrl-c", Action::Copy),
+    ("editing", "ctrl-x", Action::Cut),
+    ("editing", "ctrl-v", Action::Paste),
+    ("editing", "backspace", Action::DeleteBackward),
+    ("editing", "delete", Action::DeleteForward),
+    ("editing", "left", Action::CursorLeft),
+    ("editing", "right", Action::CursorRight),
+    ("editing", "up", Action::CursorUp),
+    ("editing", "down", Action::CursorDown),
+    ("editing", "home", Action::CursorHome),
+    ("editing", "end", Action::CursorEnd),
+    ("editing", "shift-left", Action::SelectLeft),
+    ("editing", "shift-right", Action::SelectRight),
+    ("editing", "shift-up", Action::SelectUp),
+    ("editing", "shift-down", Action::SelectDown),
+    ("editing", "shift-home", Action::SelectHome),
+    ("editing", "shift-end", Action::SelectEnd),
+    ("search", "enter", Action::ApplySearch),
+    ("code", "enter", Action::InsertNewline),
+];
+
+// added on top of the defaults by the vim keymap, editing keys are handled in `vim`
+const VIM_BINDINGS: &[(&str, &str, Action)] = &[
+    ("normal", "j", Action::SelectNext),
+    ("normal", "k", Action::SelectPrevious),
+    ("normal", "g", Action::SelectFirst),
+    ("normal", "G", Action::SelectLast),
+    ("normal", "/", Action::FocusSearch),
+    ("normal", "i", Action::EditContents),
+    ("normal", "a", Action::AppendContents),
+    ("normal", "o", Action::OpenLine),
+    ("normal", ":", Action::CommandLine),
+];
+
+fn bindings_from(table: &[(&str, &str, Action)]) -> Vec<Binding> {
+    table.iter()
+        .map(|(scope, chord, action)| Binding {
+            scope: Scope::from_name(scope).expect("invalid default scope"),
+            chord: KeyChord::parse(chord).expect("invalid default key"),
+            action: Some(*action),
+        })
+        .collect()
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap { bindings: bindings_from(DEFAULT_BINDINGS) }
+    }
+}
+
+#[allow(dead_code)]
+impl Keymap {
+    pub fn vim() -> Keymap {
+        let mut keymap = Keymap::default();
+        for binding in bindings_from(VIM_BINDINGS) {
+            keymap.bind(binding.scope, binding.chord, binding.action);
+        }
+        keymap
+    }
+
+    // the keymap file maps scopes to `"key": "action"` objects, `null` unbinds a key
+    pub fn load(mut self, path: &str) -> Result<Keymap, Vec<String>> {
+        let text = fs::read_to_string(path).map_err(|err| vec![format!("cannot read {}: {}", path, err)])?;
+        let overrides = serde_json::from_str::<HashMap<String, HashMap<String, Option<String>>>>(&text)
+            .map_err(|err| vec![format!("{} is not a valid keymap: {}", path, err)])?;
+        self.apply(&overrides)?;
+        Ok(self)
+    }
+
+    pub fn apply(&mut self, overrides: &HashMap<String, HashMap<String, Option<String>>>) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        for (scope_name, bindings) in overrides {
+            let scope = match Scope::from_name(scope_name) {
+                Some(scope) => scope,
+                None => {
+                    errors.push(format!("unknown mode '{}', expected one of global, editing, normal, search, title, language, code", scope_name));
+                    continue;
+                }
+            };
+            for (chord, action_name) in bindings {
+                let chord = match KeyChord::parse(chord) {
+                    Ok(chord) => chord,
+                    Err(err) => {
+                        errors.push(format!("{}: {}", scope_name, err));
+                        continue;
+                    }
+                };
+                let action = match action_name {
+                    Some(name) => match Action::from_name(name) {
+                        Some(action) => Some(action),
+                        None => {
+                            errors.push(format!("{}: {}: unknown action '{}'", scope_name, chord, name));
+                            continue;
+                        }
+                    },
+                    None => None
+                };
+                self.bind(scope, chord, action);
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => {
+                errors.sort();
+                Err(errors)
+            }
+        }
+    }
+
+    pub fn bind(&mut self, scope: Scope, chord: KeyChord, action: Option<Action>) {
+        self.bindings.retain(|binding| !(binding.scope == scope && binding.chord == chord));
+        self.bindings.push(Binding { scope, chord, action });
+    }
+
+    pub fn action(&self, mode: InputMode, key: &KeyEvent) -> Option<Action> {
+        let chord = KeyChord::from_event(key);
+        for scope in Scope::lookup_order(mode) {
+            if let Some(binding) = self.bindings.iter().find(|b| b.scope == scope && b.chord == chord) {
+                return binding.action;
+            }
+        }
+        None
+    }
+
+    // every key reaching an action in `mode`, shadowed bindings left out
+    pub fn bindings_for(&self, mode: InputMode) -> Vec<(KeyChord, Action)> {
+        let mut result: Vec<(KeyChord, Action)> = vec![];
+        let mut seen: Vec<KeyChord> = vec![];
+        for scope in Scope::lookup_order(mode) {
+            for binding in self.bindings.iter().filter(|b| b.scope == scope) {
+                if seen.contains(&binding.chord) {
+                    continue;
+                }
+                seen.push(binding.chord);
+                if let Some(action) = binding.action {
+                    result.push((binding.chord, action));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn keys_for(&self, mode: InputMode, action: Action) -> Vec<KeyChord> {
+        self.bindings_for(mode).into_iter().filter(|(_, a)| *a == action).map(|(chord, _)| chord).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use super::Keymap;
+    use crate::key::{Action, InputMode};
+
+    fn overrides(scope: &str, key: &str, action: Option<&str>) -> HashMap<String, HashMap<String, Option<String>>> {
+        HashMap::from([(scope.to_string(), HashMap::from([(key.to_string(), action.map(String::from))]))])
+    }
+
+    #[test]
+    fn defaults_match_builtin_keys() {
+        let keymap = Keymap::default();
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(InputMode::Normal, &ctrl_s), Some(Action::Save));
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(keymap.action(InputMode::EditingCode, &esc), Some(Action::FocusList));
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(keymap.action(InputMode::EditingTitle, &enter), None);
+    }
+
+    #[test]
+    fn focus_keys_work_everywhere() {
+        let keymap = Keymap::default();
+        let alt_4 = KeyEvent::new(KeyCode::Char('4'), KeyModifiers::ALT);
+        assert_eq!(keymap.action(InputMode::Normal, &alt_4), Some(Action::FocusLanguage));
+        assert_eq!(keymap.action(InputMode::EditingCode, &alt_4), Some(Action::FocusLanguage));
+        let back_tab = KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(InputMode::EditingTitle, &back_tab), Some(Action::PreviousField));
+    }
+
+    #[test]
+    fn overrides_rebind_and_unbind() {
+        let mut keymap = Keymap::default();
+        keymap.apply(&overrides("normal", "ctrl-w", Some("save"))).unwrap();
+        keymap.apply(&overrides("normal", "ctrl-s", None)).unwrap();
+        let ctrl_w = KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(InputMode::Normal, &ctrl_w), Some(Action::Save));
+        assert_eq!(keymap.action(InputMode::Normal, &ctrl_s), None);
+    }
+
+    #[test]
+    fn config_table_wins_over_keymap_file() {
+        let path = "./target/temp_keymap.json";
+        std::fs::write(path, r#"{"normal": {"ctrl-w": "save", "ctrl-o": "new_note"}}"#).unwrap();
+        let mut keymap = Keymap::default().load(path).unwrap();
+        keymap.apply(&overrides("normal", "ctrl-w", Some("quit"))).unwrap();
+        let ctrl_w = KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        let ctrl_o = KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(InputMode::Normal, &ctrl_w), Some(Action::Quit));
+        assert_eq!(keymap.action(InputMode::Normal, &ctrl_o), Some(Action::NewNote));
+    }
+
+    #[test]
+    fn vim_keys_only_in_vim_keymap() {
+        let j = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE);
+        assert_eq!(Keymap::default().action(InputMode::Normal, &j), None);
+        assert_eq!(Keymap::vim().action(InputMode::Normal, &j), Some(Action::SelectNext));
+        assert_eq!(Keymap::vim().action(InputMode::EditingSearch, &j), None);
+    }
+
+    #[test]
+    fn invalid_bindings_are_reported() {
+        let mut keymap = Keymap::default();
+        assert!(keymap.apply(&overrides("normal", "ctrl-w", Some("explode"))).is_err());
+        assert!(keymap.apply(&overrides("nowhere", "ctrl-w", Some("save"))).is_err());
+        assert!(keymap.apply(&overrides("normal", "ctrl-", Some("save"))).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{error::Error, io};
+use clap::Parser;
 use tui::{
     backend::{CrosstermBackend},
     Terminal,
@@ -14,38 +15,152 @@ mod view;
 mod key;
 mod note;
 mod git;
-mod env;
+mod config;
+mod editor;
+mod input;
+mod clipboard;
+mod state;
+mod keymap;
+mod vim;
+mod palette;
+mod setup;
+mod cli;
+mod pick;
+mod placeholder;
+mod runner;
+mod import;
+mod export;
 
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // load env data
-    let env_data = match env::EnvData::check_env_file_exists() {
-        true  => env::EnvData::load().unwrap(),
-        false => env::EnvData::new().unwrap()
-    };
+fn exit_with(message: &str, errors: &[String]) -> ! {
+    eprintln!("{}", message);
+    for err in errors {
+        eprintln!("  {}", err);
+    }
+    std::process::exit(1);
+}
 
-    let git_path = env_data.get_git_folder_path();
+type Term = Terminal<CrosstermBackend<io::Stdout>>;
 
-    // setup terminal
+fn setup_terminal(mouse_capture: bool) -> io::Result<Term> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    // create app and run it
-    let app = view::App::default();
-
-    let res = app::run_app(&git_path, &mut terminal, app);
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if mouse_capture {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    Terminal::new(CrosstermBackend::new(stdout))
+}
 
-    // restore terminal
+fn restore_terminal(terminal: &mut Term) -> io::Result<()> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
-    terminal.show_cursor()?;
+    terminal.show_cursor()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = cli::Cli::parse();
+    // the widget scripts need no repository
+    if let Some(command @ cli::Command::ShellInit { .. }) = cli.command {
+        std::process::exit(cli::run(command, &[], &config::Theme::default()));
+    }
+
+    // load config
+    let config_path = match cli.config.or_else(config::Config::default_path) {
+        Some(path) => path,
+        None => exit_with("No config directory found, pass --config", &[])
+    };
+    let (mut config, migrated) = match config::Config::load_or_migrate(&config_path, &std::env::current_dir()?) {
+        Ok(loaded) => loaded,
+        Err(err) => exit_with(&err.to_string(), &[])
+    };
+    if let Some(message) = migrated {
+        eprintln!("{}", message);
+    }
+    let configured = config.repository.is_some() || !config.profiles.is_empty() || cli.repo.is_some();
+    if !configured && cli.command.is_some() {
+        exit_with("No snippet repository configured, start the tool without a command to set one up", &[]);
+    }
+    if !configured {
+        // first run
+        let mut terminal = setup_terminal(false)?;
+        let result = setup::run(&mut terminal);
+        restore_terminal(&mut terminal)?;
+        match result? {
+            Some(result) => {
+                config.repository = Some(result.repository);
+                config.sync = result.sync;
+                config.save(&config_path)?;
+                eprintln!("Saved the settings to {}", config_path.display());
+            }
+            None => return Ok(())
+        }
+    }
+    let libraries = match config.libraries(cli.repo.as_deref()) {
+        Ok(libraries) => libraries,
+        Err(err) => exit_with(&format!("Invalid profiles in {}:", config_path.display()), &[err])
+    };
+    let active = match cli.profile.as_deref().or(config.profile.as_deref()) {
+        // `--repo` alone opens that repository
+        Some(_) if cli.repo.is_some() && cli.profile.is_none() => Some(0),
+        Some(name) => match config::find_library(&libraries, name) {
+            Ok(active) => active,
+            Err(err) => exit_with(&err, &[])
+        },
+        None => Some(0)
+    };
+    let theme = match config.theme.theme() {
+        Ok(theme) => theme,
+        Err(err) => exit_with(&format!("Invalid theme in {}:", config_path.display()), &[err])
+    };
+    if let Some(command) = cli.command {
+        let libraries = match active {
+            Some(index) => vec![libraries[index].clone()],
+            None => libraries
+        };
+        std::process::exit(cli::run(command, &libraries, &theme));
+    }
+
+    let settings = config.settings.clone();
+    let keymap = match settings.vim_mode {
+        true => keymap::Keymap::vim(),
+        false => keymap::Keymap::default()
+    };
+    let mut keymap = match &settings.keymap_file {
+        Some(path) => match keymap.load(&config::expand_home(path)) {
+            Ok(keymap) => keymap,
+            Err(errors) => exit_with(&format!("Invalid key bindings in {}:", path), &errors)
+        },
+        None => keymap
+    };
+    // the `[keymap]` tables come last, they win over `keymap_file`
+    if let Err(errors) = keymap.apply(&config.keymap_overrides()) {
+        exit_with(&format!("Invalid key bindings in {}:", config_path.display()), &errors);
+    }
+
+    // setup terminal
+    let mut terminal = setup_terminal(settings.mouse_capture)?;
+
+    // create app and run it
+    let app = view::App {
+        settings,
+        theme,
+        keymap,
+        libraries,
+        active,
+        runners: runner::runners(&config.runners),
+        ..Default::default()
+    };
+
+    let res = app::run_app(&mut terminal, app);
+
+    // restore terminal
+    restore_terminal(&mut terminal)?;
 
     if let Err(err) = res {
         println!("{:?}", err)
@@ -1,20 +1,86 @@
 use std::fs;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::result::Result;
 use std::error::Error;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::path::Path;
+use std::ffi::OsStr;
+use ignore::WalkBuilder;
 use serde::{Serialize, Deserialize};
 extern crate serde_json;
 
 type EResult<T> = Result<T, Box<dyn Error>>;
 
-#[derive(Serialize, Deserialize, Debug)]
+// templates for new snippets, inside the repository but not listed as snippets
+pub const TEMPLATE_DIR: &str = ".templates";
+
+// gitignore syntax, files it matches are not snippets
+pub const IGNORE_FILE: &str = ".snippetignore";
+
+// the languages known by name: the name used here, its file extension, VS Code's id, other spellings and extensions
+type Language = (&'static str, &'static str, &'static str, &'static [&'static str]);
+
+const LANGUAGES: &[Language] = &[
+    ("rust", "rs", "rust", &["rs"]),
+    ("python", "py", "python", &["py", "python3"]),
+    ("javascript", "js", "javascript", &["js", "node", "mjs", "cjs"]),
+    ("typescript", "ts", "typescript", &["ts"]),
+    ("sh", "sh", "shellscript", &["shell"]),
+    ("bash", "sh", "shellscript", &[]),
+    ("zsh", "sh", "shellscript", &[]),
+    ("fish", "fish", "fish", &[]),
+    ("powershell", "ps1", "powershell", &["ps1"]),
+    ("c", "c", "c", &["h"]),
+    ("cpp", "cpp", "cpp", &["c++", "cc", "cxx", "hpp"]),
+    ("csharp", "cs", "csharp", &["c#", "cs"]),
+    ("go", "go", "go", &["golang"]),
+    ("java", "java", "java", &[]),
+    ("kotlin", "kt", "kotlin", &["kt"]),
+    ("ruby", "rb", "ruby", &["rb"]),
+    ("perl", "pl", "perl", &["pl"]),
+    ("lua", "lua", "lua", &[]),
+    ("sql", "sql", "sql", &[]),
+    ("html", "html", "html", &["htm"]),
+    ("css", "css", "css", &[]),
+    ("json", "json", "json", &[]),
+    ("yaml", "yaml", "yaml", &["yml"]),
+    ("toml", "toml", "toml", &[]),
+    ("markdown", "md", "markdown", &["md"]),
+    ("vim", "vim", "vim", &[]),
+    ("text", "txt", "plaintext", &["txt", ""]),
+];
+
+fn find_language(name: &str) -> Option<&'static Language> {
+    let name = name.trim().to_lowercase();
+    LANGUAGES.iter().find(|(language, extension, _, aliases)| *language == name || *extension == name || aliases.contains(&name.as_str()))
+}
+
+// the name used here for a language, its extension or another spelling of it
+pub fn language_name(name: &str) -> String {
+    find_language(name).map_or_else(|| name.trim().to_lowercase(), |(language, _, _, _)| String::from(*language))
+}
+
+// VS Code's id for a language, unknown ones keep their name
+pub fn vscode_id(name: &str) -> String {
+    find_language(name).map_or_else(|| name.trim().to_lowercase(), |(_, _, id, _)| String::from(*id))
+}
+
+pub fn from_vscode_id(id: &str) -> String {
+    let id = id.trim().to_lowercase();
+    LANGUAGES.iter()
+        .find(|(_, _, vscode, _)| *vscode == id)
+        .map_or_else(|| language_name(&id), |(language, _, _, _)| String::from(*language))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Note {
     pub file_path: String,
     pub title: String,
     pub language: String,
     pub contents: String,
+    // older snippets have no tags
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl Clone for Note {
@@ -23,7 +89,8 @@ impl Clone for Note {
             file_path: self.file_path.clone(),
             title: self.title.clone(),
             language: self.language.clone(),
-            contents: self.contents.clone()
+            contents: self.contents.clone(),
+            tags: self.tags.clone()
         }
     }
 }
@@ -43,67 +110,198 @@ impl Note {
             file_path: String::from(final_path),
             language: String::from(language),
             title: String::from(title),
-            contents: String::from(contents)
+            contents: String::from(contents),
+            tags: vec![]
         }
     }
 
+    // file extension matching `language`, used when the note leaves the app
+    pub fn language_extension(&self) -> &str {
+        find_language(&self.language).map_or("txt", |(_, extension, _, _)| extension)
+    }
+
     fn check_file_exist(&self) -> bool {
         Path::new(&self.file_path).exists()
     }
 
     pub fn delete(&self) -> EResult<()> {
         match self.check_file_exist() {
-            true => {
-                fs::remove_file(&self.file_path).expect("File delete failed");
-                Ok(())
-            },
+            true => Ok(fs::remove_file(&self.file_path)?),
             false => Err("File not exist".into())
         }
     }
 
+    // written beside the old copy and renamed over it, a failed save leaves the old copy alone
     pub fn save(&self) -> EResult<String> {
-        self.delete();
         let path = Path::new(&self.file_path);
-        let prefix = path.parent().unwrap();
-        std::fs::create_dir_all(prefix).unwrap();
-        let mut f = OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .create_new(true)
-                    .open(&self.file_path)?;
-
-        match f.write_all(serde_json::to_string(&self)?.as_bytes()) {
-            Ok(_) => Ok(String::from(&self.file_path)),
-            Err(_) => panic!("write error")
+        let file_name = path.file_name().and_then(OsStr::to_str).ok_or("no file name")?;
+        if let Some(prefix) = path.parent() {
+            fs::create_dir_all(prefix)?;
+        }
+        let temp = path.with_file_name(format!(".{}.tmp", file_name));
+        let written = fs::write(&temp, serde_json::to_string(&self)?).and_then(|_| fs::rename(&temp, path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(err.into());
+        }
+        Ok(String::from(&self.file_path))
+    }
+
+    // saves in place of `old`, which is removed only once the new file is written
+    pub fn replace(&self, old: &Note) -> EResult<String> {
+        let saved = self.save()?;
+        if old.file_path != self.file_path && !same_file(Path::new(&old.file_path), Path::new(&self.file_path)) {
+            let _ = old.delete();
         }
+        Ok(saved)
     }
     
+    // says which of reading, decoding or parsing failed
     pub fn load(path: &str) -> EResult<Self> {
-        match Path::new(path).exists() {
-            true  => {},
-            false => panic!("File not existed, load failed!")
+        Note::parse(path, &read_text(path)?)
+    }
+
+    fn parse(path: &str, text: &str) -> EResult<Self> {
+        let note = serde_json::from_str::<Note>(text).map_err(|err| format!("not a snippet: {}", err))?;
+        // the stored path goes stale when the repository moves
+        Ok(Note{
+            file_path: String::from(path),
+            language: note.language,
+            title: note.title,
+            contents: note.contents,
+            tags: note.tags
+        })
+    }
+}
+
+// titles and languages become a file and a folder of the library, they must stay inside it
+pub fn check_name(what: &str, name: &str) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("The {} must not be empty", what));
+    }
+    if name.contains(['/', '\\']) || name == ".." || Path::new(name).is_absolute() {
+        return Err(format!("The {} '{}' must not contain '/' or '\\' or be '..'", what, name));
+    }
+    // hidden files and folders are not loaded
+    if name.starts_with('.') {
+        return Err(format!("The {} '{}' must not start with '.'", what, name));
+    }
+    Ok(())
+}
+
+// a rename that only changes case keeps one file on case-insensitive file systems
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        #[cfg(unix)]
+        (Ok(a), Ok(b)) => {
+            use std::os::unix::fs::MetadataExt;
+            a.dev() == b.dev() && a.ino() == b.ino()
+        }
+        #[cfg(not(unix))]
+        (Ok(_), Ok(_)) => a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase(),
+        _ => false
+    }
+}
+
+fn read_text(path: &str) -> EResult<String> {
+    let mut buf = vec![];
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buf))
+        .map_err(|err| format!("cannot read: {}", err))?;
+    Ok(String::from_utf8(buf).map_err(|err| format!("not valid UTF-8: {}", err.utf8_error()))?)
+}
+
+// snippets are a JSON object, other Markdown is documentation, a merge conflict stays a broken snippet
+fn looks_like_snippet(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with('{') || text.starts_with("<<<<<<< ")
+}
+
+// a file that could not be loaded, the rest of the library still opens
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub path: String,
+    pub message: String,
+}
+
+// the file an error of the walk is about, the repository when it has none
+fn walk_problem(base_url: &str, err: &ignore::Error) -> Problem {
+    match err {
+        ignore::Error::WithPath { path, err } => Problem { path: path.display().to_string(), message: format!("cannot read: {}", err) },
+        ignore::Error::WithDepth { err, .. } => walk_problem(base_url, err),
+        ignore::Error::Partial(errors) if !errors.is_empty() => walk_problem(base_url, &errors[0]),
+        ignore::Error::Loop { child, .. } => Problem { path: child.display().to_string(), message: err.to_string() },
+        _ => Problem { path: String::from(base_url), message: format!("cannot read: {}", err) }
+    }
+}
+
+// every snippet below `base_url`, shared by the TUI and the subcommands
+// hidden folders and whatever `.gitignore` or `.snippetignore` match are skipped
+pub fn load_all_markdown(base_url: &str) -> (Vec<Note>, Vec<Problem>) {
+    let mut vecs: Vec<Note> = vec![];
+    let mut problems: Vec<Problem> = vec![];
+    // the rules of the repository itself, also before it is a git repository
+    let walk = WalkBuilder::new(base_url)
+        .hidden(true)
+        .parents(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .build();
+    for entry in walk {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                problems.push(walk_problem(base_url, &err));
+                continue;
+            }
+        };
+        // a broken ignore file is read as far as possible
+        if let Some(err) = entry.error() {
+            problems.push(walk_problem(base_url, err));
         }
-        let mut file = File::open(path)?;
-        let mut buf = vec![];
-        match file.read_to_end(&mut buf).is_ok() {
-            true => {
-                let note = serde_json::from_slice::<Note>(&buf[..])?;
-                Ok(Note{
-                    file_path: note.file_path,
-                    language: note.language,
-                    title: note.title,
-                    contents: note.contents
-                })
-            },
-            false => Err("load error".into())
+        let file_name = entry.path();
+        let file_extension = file_name.extension().and_then(OsStr::to_str);
+        if file_extension == Some("md") && entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            let path = file_name.display().to_string();
+            let loaded = read_text(&path).and_then(|text| match looks_like_snippet(&text) {
+                true => Note::parse(&path, &text).map(Some),
+                false => Ok(None)
+            });
+            match loaded {
+                Ok(Some(note)) => vecs.push(note),
+                Ok(None) => {}
+                Err(err) => problems.push(Problem { path, message: err.to_string() })
+            }
         }
     }
+    (vecs, problems)
+}
+
+// the templates of the repository at `base_url` by name, files that don't load are left out
+pub fn load_templates(base_url: &str) -> Vec<(String, Note)> {
+    let dir = Path::new(base_url).join(TEMPLATE_DIR);
+    let mut templates: Vec<(String, Note)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(OsStr::to_str) == Some("md"))
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+                Note::load(&path.display().to_string()).ok().map(|note| (name, note))
+            })
+            .collect(),
+        Err(_) => vec![]
+    };
+    templates.sort_by(|a, b| a.0.cmp(&b.0));
+    templates
 }
 
 #[cfg(test)]
 mod test{
-    use super::Note;
+    use super::{check_name, from_vscode_id, language_name, load_all_markdown, load_templates, vscode_id, Note, IGNORE_FILE, TEMPLATE_DIR};
 
+    #[allow(clippy::redundant_locals)]
     fn create_new_object(title: &str) -> Note {
         let file_path = "./target/temp";
         let language = "python";
@@ -116,6 +314,7 @@ mod test{
         )
     }
 
+    #[allow(unused_variables)]
     fn save_a_object(note: &Note) -> &str {
         let rst: bool;
         match note.save() {
@@ -138,6 +337,24 @@ mod test{
     }
 
     #[test]
+    fn note_language_extension() {
+        let mut note = create_new_object("extension");
+        assert_eq!(note.language_extension(), "py");
+        note.language = String::from("Rust");
+        assert_eq!(note.language_extension(), "rs");
+        note.language = String::from("unknown");
+        assert_eq!(note.language_extension(), "txt");
+    }
+
+    #[test]
+    fn one_language_table() {
+        assert_eq!((language_name("cc"), language_name("H"), language_name("Python3"), language_name("")), (String::from("cpp"), String::from("c"), String::from("python"), String::from("text")));
+        assert_eq!((vscode_id("h"), vscode_id("bash"), vscode_id("elixir")), (String::from("c"), String::from("shellscript"), String::from("elixir")));
+        assert_eq!((from_vscode_id("shellscript"), from_vscode_id("plaintext"), from_vscode_id("cpp")), (String::from("sh"), String::from("text"), String::from("cpp")));
+    }
+
+    #[test]
+    #[allow(clippy::len_zero)]
     fn note_should_be_created() {
         let note = create_new_object("python");
         let path = save_a_object(&note);
@@ -155,6 +372,107 @@ mod test{
     }
 
     #[test]
+    fn note_without_tags_loads() {
+        let note: Note = serde_json::from_str(r#"{"file_path":"a/sh/ls.md","title":"ls","language":"sh","contents":"ls"}"#).unwrap();
+        assert!(note.tags.is_empty());
+    }
+
+    #[test]
+    fn templates_are_not_snippets() {
+        let base = "./target/temp_templates";
+        let _ = std::fs::remove_dir_all(base);
+        Note::new(base, "rust", "main", "fn main() {}").save().unwrap();
+        let mut template = Note::new(base, TEMPLATE_DIR, "rust", "fn main() {\n}");
+        template.language = String::from("rust");
+        template.tags = vec![String::from("bin")];
+        template.save().unwrap();
+        let (notes, _) = load_all_markdown(base);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "main");
+        let templates = load_templates(base);
+        assert_eq!(templates.len(), 1);
+        assert_eq!((templates[0].0.as_str(), templates[0].1.language.as_str()), ("rust", "rust"));
+        assert_eq!(templates[0].1.tags, vec!["bin"]);
+    }
+
+    #[test]
+    fn broken_files_are_reported() {
+        let base = "./target/temp_problems";
+        let _ = std::fs::remove_dir_all(base);
+        Note::new(base, "sh", "ok", "ls").save().unwrap();
+        std::fs::write(format!("{}/sh/conflict.md", base), "<<<<<<< HEAD\n{}\n=======\n{}\n>>>>>>> theirs\n").unwrap();
+        std::fs::write(format!("{}/sh/latin1.md", base), b"\xe9t\xe9").unwrap();
+        std::fs::create_dir_all(format!("{}/folder.md", base)).unwrap();
+        let (notes, mut problems) = load_all_markdown(base);
+        assert_eq!(notes.len(), 1);
+        problems.sort_by(|a, b| a.path.cmp(&b.path));
+        let messages: Vec<(&str, bool)> = problems.iter()
+            .map(|problem| (problem.path.as_str(), problem.message.starts_with("not a snippet") || problem.message.starts_with("not valid UTF-8")))
+            .collect();
+        assert_eq!(messages, vec![("./target/temp_problems/sh/conflict.md", true), ("./target/temp_problems/sh/latin1.md", true)]);
+        assert!(Note::load("./target/temp_problems/missing.md").unwrap_err().to_string().starts_with("cannot read"));
+    }
+
+    #[test]
+    fn failed_save_keeps_the_old_file() {
+        let base = "./target/temp_replace";
+        let _ = std::fs::remove_dir_all(base);
+        let old = Note::new(base, "sh", "list", "ls");
+        old.save().unwrap();
+        // a folder in the way makes the write fail
+        std::fs::create_dir_all(format!("{}/sh/blocked.md", base)).unwrap();
+        assert!(Note::new(base, "sh", "blocked", "ls -la").replace(&old).is_err());
+        assert_eq!(Note::load(&old.file_path).unwrap().contents, "ls");
+        let renamed = Note::new(base, "sh", "list all", "ls -la");
+        renamed.replace(&old).unwrap();
+        assert!(!std::path::Path::new(&old.file_path).exists());
+        assert_eq!(Note::load(&renamed.file_path).unwrap().contents, "ls -la");
+        let (notes, problems) = load_all_markdown(base);
+        assert_eq!((notes.len(), problems.len()), (1, 0));
+    }
+
+    #[test]
+    fn names_stay_inside_the_library() {
+        assert!(check_name("title", "list files").is_ok());
+        assert!(check_name("title", "c++ hello").is_ok());
+        assert!(check_name("title", "wait...").is_ok());
+        for name in ["", " ", "../../x", "/etc/foo", "a/b", "a\\b", "..", ".hidden"] {
+            assert!(check_name("title", name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn broken_ignore_files_are_reported() {
+        let base = "./target/temp_bad_ignore";
+        let _ = std::fs::remove_dir_all(base);
+        Note::new(base, "sh", "ok", "ls").save().unwrap();
+        std::fs::write(format!("{}/{}", base, IGNORE_FILE), "drafts/\n{unclosed\n").unwrap();
+        let (notes, problems) = load_all_markdown(base);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, format!("{}/{}", base, IGNORE_FILE));
+    }
+
+    #[test]
+    fn ignore_rules_and_documentation() {
+        let base = "./target/temp_ignore";
+        let _ = std::fs::remove_dir_all(base);
+        Note::new(base, "sh", "ok", "ls").save().unwrap();
+        Note::new(base, "vendor", "copied", "ls").save().unwrap();
+        Note::new(base, "scratch", "draft", "ls").save().unwrap();
+        Note::new(&format!("{}/.git", base), "sh", "object", "ls").save().unwrap();
+        std::fs::write(format!("{}/.gitignore", base), "vendor/\n").unwrap();
+        std::fs::write(format!("{}/{}", base, IGNORE_FILE), "scratch/\n").unwrap();
+        std::fs::write(format!("{}/README.md", base), "# my snippets\n\n{ not json }").unwrap();
+        std::fs::write(format!("{}/sh/notes.md", base), "Plain notes about `ls`").unwrap();
+        let (notes, problems) = load_all_markdown(base);
+        let titles: Vec<&str> = notes.iter().map(|note| note.title.as_str()).collect();
+        assert_eq!(titles, vec!["ok"]);
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    #[allow(unused_variables, clippy::bool_assert_comparison, clippy::needless_late_init)]
     fn note_should_be_deleted() {
         let note = create_new_object("anathor");
         assert_eq!(note.check_file_exist(), false);
@@ -0,0 +1,68 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+
+use crate::key::{Action, InputMode, KeyChord};
+use crate::keymap::Keymap;
+
+#[derive(Debug, Clone)]
+pub struct PaletteState {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl PaletteState {
+    pub fn new() -> Self {
+        PaletteState { query: String::new(), selected: 0 }
+    }
+}
+
+// actions available in `mode` matching `query`, best match first
+pub fn palette_entries(keymap: &Keymap, mode: InputMode, vim: bool, query: &str) -> Vec<(Action, Vec<KeyChord>)> {
+    let matcher = SkimMatcherV2::default();
+    let mut entries: Vec<(i64, Action)> = Action::ALL.iter()
+        .copied()
+        .filter(|action| *action != Action::CommandPalette)
+        .filter(|action| mode != InputMode::Normal || !action.is_editing())
+        .filter(|action| vim || !action.is_vim())
+        .filter_map(|action| {
+            let text = format!("{} {}", action.description(), action.name());
+            matcher.fuzzy_match(&text, query).map(|score| (score, action))
+        })
+        .collect();
+    // keep the declaration order for equal scores
+    entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    entries.into_iter()
+        .map(|(_, action)| (action, keymap.keys_for(mode, action)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::palette_entries;
+    use crate::key::{Action, InputMode};
+    use crate::keymap::Keymap;
+
+    #[test]
+    fn finds_action_with_keys() {
+        let entries = palette_entries(&Keymap::default(), InputMode::Normal, false, "delete snippet");
+        let (action, keys) = &entries[0];
+        assert_eq!(*action, Action::DeleteNote);
+        assert_eq!(keys.iter().map(|k| k.to_string()).collect::<Vec<_>>(), vec!["ctrl-d"]);
+    }
+
+    #[test]
+    fn editing_actions_hidden_in_list() {
+        let entries = palette_entries(&Keymap::default(), InputMode::Normal, false, "");
+        assert!(entries.iter().all(|(action, _)| *action != Action::Paste));
+        let entries = palette_entries(&Keymap::default(), InputMode::EditingCode, false, "");
+        assert!(entries.iter().any(|(action, _)| *action == Action::Paste));
+    }
+
+    #[test]
+    fn vim_actions_only_in_vim_mode() {
+        let entries = palette_entries(&Keymap::default(), InputMode::Normal, false, "");
+        assert!(entries.iter().all(|(action, _)| !action.is_vim()));
+        let entries = palette_entries(&Keymap::vim(), InputMode::Normal, true, "");
+        assert!(entries.iter().any(|(action, _)| *action == Action::CommandLine));
+    }
+}
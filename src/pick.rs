@@ -0,0 +1,221 @@
+use std::io;
+use clap::ValueEnum;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use tui::{backend::CrosstermBackend, Terminal};
+
+use crate::config::Theme;
+use crate::note::Note;
+use crate::placeholder::{FillForm, Filled, PlaceholderMemory};
+use crate::view::picker_ui;
+
+pub struct PickerState {
+    pub query: String,
+    pub selected: usize,
+    pub notes: Vec<Note>,
+    pub labels: Vec<String>,
+    // open while the placeholders of the chosen snippet are filled in
+    pub form: Option<FillForm>,
+    pub memory: PlaceholderMemory,
+}
+
+pub enum Picked {
+    Text(String),
+    Cancel,
+}
+
+impl PickerState {
+    pub fn new(notes: Vec<Note>, labels: Vec<String>, query: &str, memory: PlaceholderMemory) -> Self {
+        PickerState { query: String::from(query), selected: 0, notes, labels, form: None, memory }
+    }
+
+    // indexes of the notes matching the query, best match first
+    pub fn matches(&self) -> Vec<usize> {
+        if self.query.is_empty() {
+            let mut all: Vec<usize> = (0..self.notes.len()).collect();
+            all.sort_by_key(|i| &self.labels[*i]);
+            return all;
+        }
+        let matcher = SkimMatcherV2::default();
+        let mut found: Vec<(i64, usize)> = self.notes.iter()
+            .enumerate()
+            .filter_map(|(i, note)| {
+                let text = format!("{} {}", note.title, note.language);
+                matcher.fuzzy_match(&text, &self.query).map(|score| (score, i))
+            })
+            .collect();
+        found.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        found.into_iter().map(|(_, i)| i).collect()
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Picked> {
+        if let Some(form) = &mut self.form {
+            match form.handle_key(key)? {
+                Filled::Done(text) => {
+                    self.memory.remember(&form.values());
+                    return Some(Picked::Text(text));
+                }
+                // back to the list
+                Filled::Cancel => self.form = None
+            }
+            return None;
+        }
+        let matches = self.matches();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Picked::Cancel),
+            KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return Some(Picked::Cancel),
+            KeyCode::Enter => {
+                let contents = &self.notes[*matches.get(self.selected)?].contents;
+                match FillForm::new(contents, &mut self.memory) {
+                    Some(form) => self.form = Some(form),
+                    None => return Some(Picked::Text(contents.clone()))
+                }
+            }
+            KeyCode::Down | KeyCode::Tab => self.selected = (self.selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Char('n') if ctrl => self.selected = (self.selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.selected = 0;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+// draws the picker on stderr so stdout stays free for the chosen snippet
+pub fn run(state: &mut PickerState, theme: &Theme) -> io::Result<Option<String>> {
+    enable_raw_mode()?;
+    let mut stderr = io::stderr();
+    execute!(stderr, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stderr))?;
+    let result = pick(&mut terminal, state, theme);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+fn pick(terminal: &mut Terminal<CrosstermBackend<io::Stderr>>, state: &mut PickerState, theme: &Theme) -> io::Result<Option<String>> {
+    loop {
+        terminal.draw(|f| picker_ui(f, state, theme))?;
+        if let Event::Key(key) = event::read()? {
+            match state.handle_key(&key) {
+                Some(Picked::Text(text)) => return Ok(Some(text)),
+                Some(Picked::Cancel) => return Ok(None),
+                None => {}
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// a widget inserting the picked snippet at the cursor, bound to alt-s
+pub fn shell_init(shell: Shell, program: &str) -> String {
+    let script = match shell {
+        Shell::Bash => r#"__snippet_pick() {
+  local snippet
+  snippet="$(PROGRAM pick </dev/tty)" || return
+  READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}${snippet}${READLINE_LINE:$READLINE_POINT}"
+  READLINE_POINT=$((READLINE_POINT + ${#snippet}))
+}
+bind -m emacs-standard -x '"\es": __snippet_pick'
+bind -m vi-insert -x '"\es": __snippet_pick'
+"#,
+        Shell::Zsh => r#"__snippet_pick() {
+  local snippet
+  snippet="$(PROGRAM pick </dev/tty)"
+  if [[ $? -eq 0 ]]; then
+    LBUFFER="${LBUFFER}${snippet}"
+  fi
+  zle reset-prompt
+}
+zle -N __snippet_pick
+bindkey '\es' __snippet_pick
+"#,
+        Shell::Fish => r#"function __snippet_pick
+    set -l snippet (PROGRAM pick </dev/tty | string collect)
+    and commandline -i -- $snippet
+    commandline -f repaint
+end
+bind \es __snippet_pick
+"#,
+    };
+    script.replace("PROGRAM", &quote(program))
+}
+
+// single quotes work the same in all three shells as long as the path has none
+fn quote(program: &str) -> String {
+    match program.contains(|c: char| c.is_whitespace() || "'\"$`\\".contains(c)) {
+        true => format!("'{}'", program.replace('\'', r"'\''")),
+        false => String::from(program)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{quote, shell_init, Picked, PickerState, Shell};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use crate::note::Note;
+    use crate::placeholder::PlaceholderMemory;
+
+    fn picker(query: &str) -> PickerState {
+        let notes = vec![
+            Note::new("repo", "sh", "list files", "ls -la"),
+            Note::new("repo", "python", "hello", "print({{name:1}})"),
+        ];
+        let labels = vec![String::from("sh/list files"), String::from("python/hello")];
+        PickerState::new(notes, labels, query, PlaceholderMemory::default())
+    }
+
+    #[test]
+    fn filters_and_picks() {
+        assert_eq!(picker("").matches(), vec![1, 0]);
+        let mut state = picker("lst");
+        assert_eq!(state.matches(), vec![0]);
+        state.handle_key(&KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        state.handle_key(&KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        state.handle_key(&KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        state.handle_key(&KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert!(matches!(state.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)), Some(Picked::Text(text)) if text == "ls -la"));
+        assert!(matches!(state.handle_key(&KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)), Some(Picked::Cancel)));
+    }
+
+    #[test]
+    fn fills_placeholders_before_printing() {
+        let mut state = picker("hello");
+        assert!(state.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).is_none());
+        assert!(state.form.is_some());
+        state.handle_key(&KeyEvent::new(KeyCode::Char('0'), KeyModifiers::NONE));
+        assert!(matches!(state.handle_key(&KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)), Some(Picked::Text(text)) if text == "print(10)"));
+    }
+
+    #[test]
+    fn scripts_call_the_program() {
+        assert!(shell_init(Shell::Zsh, "snippet_rustui").contains("$(snippet_rustui pick </dev/tty)"));
+        assert!(shell_init(Shell::Fish, "/usr/bin/snippet_rustui").contains("bind \\es __snippet_pick"));
+        assert_eq!(quote("/my apps/snippet"), "'/my apps/snippet'");
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use regex::{Captures, Regex};
+use serde::{Serialize, Deserialize};
+
+use crate::state::{state_file, state_path};
+
+// remembered values, kept in the state directory of the first library
+pub const PLACEHOLDER_FILE: &str = "placeholders.json";
+
+// `{{name}}`, `{{name:default}}` or `{{name|choice,choice}}`
+const PATTERN: &str = r"\{\{\s*([A-Za-z_][\w.-]*)\s*(?::([^}|]*)|\|([^}]*))?\}\}";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    pub default: Option<String>,
+    pub choices: Vec<String>,
+}
+
+fn parse(caps: &Captures) -> Placeholder {
+    Placeholder {
+        name: String::from(&caps[1]),
+        default: caps.get(2).map(|default| String::from(default.as_str().trim())),
+        choices: caps.get(3)
+            .map(|choices| choices.as_str().split(',').map(|choice| String::from(choice.trim())).collect())
+            .unwrap_or_default(),
+    }
+}
+
+// the placeholders of `contents` in order, a name used twice is asked once
+pub fn placeholders(contents: &str) -> Vec<Placeholder> {
+    let re = Regex::new(PATTERN).unwrap();
+    let mut found: Vec<Placeholder> = vec![];
+    for caps in re.captures_iter(contents) {
+        let placeholder = parse(&caps);
+        match found.iter_mut().find(|other| other.name == placeholder.name) {
+            // a later occurrence may spell out the default or the choices
+            Some(other) => {
+                if other.default.is_none() && other.choices.is_empty() {
+                    *other = placeholder;
+                }
+            }
+            None => found.push(placeholder)
+        }
+    }
+    found
+}
+
+// rewrites `contents` piece by piece, `text` gets what lies between the placeholders
+pub fn rewrite(contents: &str, mut text: impl FnMut(&str) -> String, mut placeholder: impl FnMut(&Placeholder) -> String) -> String {
+    let re = Regex::new(PATTERN).unwrap();
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(contents) {
+        let whole = caps.get(0).unwrap();
+        out.push_str(&text(&contents[last..whole.start()]));
+        out.push_str(&placeholder(&parse(&caps)));
+        last = whole.end();
+    }
+    out.push_str(&text(&contents[last..]));
+    out
+}
+
+// replaces every placeholder with its value, unknown names are left alone
+pub fn fill(contents: &str, values: &BTreeMap<String, String>) -> String {
+    let re = Regex::new(PATTERN).unwrap();
+    re.replace_all(contents, |caps: &Captures| match values.get(&caps[1]) {
+        Some(value) => value.clone(),
+        None => String::from(&caps[0])
+    }).into_owned()
+}
+
+// the last value given to each placeholder name, kept in the library at `base` when set
+// read on first use, the state directory is only created once a value is remembered
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PlaceholderMemory {
+    values: BTreeMap<String, String>,
+    #[serde(skip)]
+    base: Option<String>,
+    #[serde(skip)]
+    loaded: bool,
+}
+
+impl PlaceholderMemory {
+    pub fn new(base: &str) -> Self {
+        PlaceholderMemory { base: Some(String::from(base)), ..Default::default() }
+    }
+
+    fn values(&mut self) -> &BTreeMap<String, String> {
+        if !self.loaded {
+            self.loaded = true;
+            if let Some(base) = &self.base {
+                if let Some(saved) = crate::state::load::<PlaceholderMemory>(&state_path(base, PLACEHOLDER_FILE)) {
+                    self.values = saved.values;
+                }
+            }
+        }
+        &self.values
+    }
+
+    pub fn remember(&mut self, values: &BTreeMap<String, String>) {
+        self.values();
+        self.values.extend(values.iter().map(|(name, value)| (name.clone(), value.clone())));
+        if let Some(base) = &self.base {
+            if let Ok(file) = state_file(base, PLACEHOLDER_FILE) {
+                let _ = crate::state::save(&file, self);
+            }
+        }
+    }
+}
+
+pub enum Filled {
+    Done(String),
+    Cancel,
+}
+
+// the form asking for the placeholder values of `contents`
+#[derive(Debug, Clone)]
+pub struct FillForm {
+    pub contents: String,
+    pub fields: Vec<Placeholder>,
+    pub values: Vec<String>,
+    pub selected: usize,
+}
+
+impl FillForm {
+    // None when `contents` has no placeholders
+    pub fn new(contents: &str, memory: &mut PlaceholderMemory) -> Option<Self> {
+        let fields = placeholders(contents);
+        if fields.is_empty() {
+            return None;
+        }
+        // the remembered value, then the default, then the first choice
+        let remembered = memory.values();
+        let values = fields.iter()
+            .map(|field| remembered.get(&field.name).cloned()
+                .or_else(|| field.default.clone())
+                .or_else(|| field.choices.first().cloned())
+                .unwrap_or_default())
+            .collect();
+        Some(FillForm { contents: String::from(contents), fields, values, selected: 0 })
+    }
+
+    pub fn values(&self) -> BTreeMap<String, String> {
+        self.fields.iter().map(|field| field.name.clone()).zip(self.values.iter().cloned()).collect()
+    }
+
+    // moves through the choices of the selected field
+    fn cycle(&mut self, forward: bool) {
+        let choices = &self.fields[self.selected].choices;
+        if choices.is_empty() {
+            return;
+        }
+        let value = &mut self.values[self.selected];
+        let index = match (choices.iter().position(|choice| choice == value), forward) {
+            (Some(i), true) => (i + 1) % choices.len(),
+            (Some(i), false) => (i + choices.len() - 1) % choices.len(),
+            (None, _) => 0
+        };
+        *value = choices[index].clone();
+    }
+
+    // a pasted line goes into the selected field
+    pub fn paste(&mut self, text: &str) {
+        self.values[self.selected].push_str(text.trim_end_matches(['\r', '\n']));
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Filled> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Filled::Cancel),
+            KeyCode::Enter => return Some(Filled::Done(fill(&self.contents, &self.values()))),
+            KeyCode::Down | KeyCode::Tab => self.selected = (self.selected + 1) % self.fields.len(),
+            KeyCode::Up | KeyCode::BackTab => self.selected = (self.selected + self.fields.len() - 1) % self.fields.len(),
+            KeyCode::Right => self.cycle(true),
+            KeyCode::Left => self.cycle(false),
+            KeyCode::Backspace => {
+                self.values[self.selected].pop();
+            }
+            KeyCode::Char('u') if ctrl => self.values[self.selected].clear(),
+            KeyCode::Char(c) if !ctrl => self.values[self.selected].push(c),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use super::{fill, placeholders, FillForm, Filled, PlaceholderMemory};
+    use crate::state::STATE_DIR;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn parses_defaults_and_choices() {
+        let found = placeholders("kubectl logs -n {{namespace}} {{pod}} --port {{port:8080}} {{env|dev, staging,prod}} {{ namespace }} {x} {{1bad}}");
+        let names: Vec<&str> = found.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["namespace", "pod", "port", "env"]);
+        assert_eq!(found[2].default.as_deref(), Some("8080"));
+        assert_eq!(found[3].choices, vec!["dev", "staging", "prod"]);
+    }
+
+    #[test]
+    fn fills_every_occurrence() {
+        let values = BTreeMap::from([(String::from("env"), String::from("prod"))]);
+        assert_eq!(fill("{{env|dev,prod}} and {{env}} {{other}}", &values), "prod and prod {{other}}");
+    }
+
+    #[test]
+    fn form_starts_from_memory() {
+        let mut memory = PlaceholderMemory::default();
+        memory.remember(&BTreeMap::from([(String::from("pod"), String::from("web-1"))]));
+        assert!(FillForm::new("no placeholders", &mut memory).is_none());
+        let mut form = FillForm::new("{{pod}} {{env|dev,prod}} {{port:8080}}", &mut memory).unwrap();
+        assert_eq!(form.values, vec!["web-1", "dev", "8080"]);
+        form.handle_key(&key(KeyCode::Tab));
+        form.handle_key(&key(KeyCode::Right));
+        form.handle_key(&key(KeyCode::Tab));
+        form.handle_key(&key(KeyCode::Char('1')));
+        match form.handle_key(&key(KeyCode::Enter)) {
+            Some(Filled::Done(text)) => assert_eq!(text, "web-1 prod 80801"),
+            _ => panic!("form not submitted")
+        }
+    }
+
+    #[test]
+    fn memory_is_saved_on_first_value() {
+        let base = "./target/temp_placeholders";
+        let _ = std::fs::remove_dir_all(base);
+        std::fs::create_dir_all(base).unwrap();
+        let mut memory = PlaceholderMemory::new(base);
+        assert!(FillForm::new("{{pod}}", &mut memory).is_some());
+        assert!(!std::path::Path::new(base).join(STATE_DIR).exists());
+        memory.remember(&BTreeMap::from([(String::from("pod"), String::from("web-1"))]));
+        let mut memory = PlaceholderMemory::new(base);
+        memory.remember(&BTreeMap::from([(String::from("env"), String::from("prod"))]));
+        let form = FillForm::new("{{pod}} {{env}}", &mut PlaceholderMemory::new(base)).unwrap();
+        assert_eq!(form.values, vec!["web-1", "prod"]);
+    }
+}
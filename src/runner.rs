@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::note::language_name;
+
+// the interpreters known without a `[runners]` table, the contents go to stdin;
+// keyed by `language_name`, aliases like `py` or `node` find their language
+const DEFAULT_RUNNERS: &[(&str, &str)] = &[
+    ("sh", "sh -s"),
+    ("bash", "bash -s"),
+    ("zsh", "zsh -s"),
+    ("fish", "fish"),
+    ("python", "python3 -"),
+    ("javascript", "node -"),
+    ("ruby", "ruby -"),
+    ("perl", "perl -"),
+    ("lua", "lua -"),
+    ("powershell", "pwsh -Command -"),
+];
+
+// the runners for each language, `overrides` come from the config, `"none"` removes one
+pub fn runners(overrides: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut runners: BTreeMap<String, String> = DEFAULT_RUNNERS.iter()
+        .map(|(language, command)| (String::from(*language), String::from(*command)))
+        .collect();
+    for (language, command) in overrides {
+        match command.as_str() {
+            "none" => runners.remove(&language_name(language)),
+            _ => runners.insert(language_name(language), command.clone())
+        };
+    }
+    runners
+}
+
+// the program and its arguments, a `{}` argument is replaced by the contents instead of piping them
+pub fn command_line(command: &str, contents: &str) -> (Vec<String>, Option<String>) {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    match words.contains(&"{}") {
+        true => (words.iter().map(|word| match *word {
+            "{}" => String::from(contents),
+            word => String::from(word)
+        }).collect(), None),
+        false => (words.iter().map(|word| String::from(*word)).collect(), Some(String::from(contents)))
+    }
+}
+
+enum Message {
+    Line(bool, String),
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputLine {
+    pub stderr: bool,
+    pub text: String,
+}
+
+// a snippet running in the background, `poll` collects what it printed so far
+pub struct Run {
+    pub command: String,
+    pub lines: Vec<OutputLine>,
+    pub started: Instant,
+    // exit status and duration, `None` status when killed by a signal
+    pub finished: Option<(Option<i32>, Duration)>,
+    pub cancelled: bool,
+    child: Child,
+    receiver: Receiver<Message>,
+    open_streams: usize,
+}
+
+fn forward<R: Read + Send + 'static>(stream: R, stderr: bool, sender: Sender<Message>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(Message::Line(stderr, line)).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break
+            }
+        }
+        let _ = sender.send(Message::Closed);
+    });
+}
+
+impl Run {
+    pub fn start(command: &str, contents: &str) -> io::Result<Run> {
+        let (words, input) = command_line(command, contents);
+        let (program, args) = match words.split_first() {
+            Some(split) => split,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty runner command"))
+        };
+        let mut process = Command::new(program);
+        process.args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // its own process group, so cancelling also stops what the snippet started
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        let mut child = process.spawn()?;
+
+        // written from a thread so a large snippet cannot block on a full pipe
+        let mut stdin = child.stdin.take();
+        thread::spawn(move || {
+            if let (Some(stdin), Some(input)) = (stdin.as_mut(), input) {
+                let _ = stdin.write_all(input.as_bytes());
+            }
+        });
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward(stdout, false, sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(stderr, true, sender);
+        }
+        Ok(Run {
+            command: String::from(command),
+            lines: vec![],
+            started: Instant::now(),
+            finished: None,
+            cancelled: false,
+            child,
+            receiver,
+            open_streams: 2,
+        })
+    }
+
+    pub fn running(&self) -> bool {
+        self.finished.is_none()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.finished {
+            Some((_, duration)) => duration,
+            None => self.started.elapsed()
+        }
+    }
+
+    // collects new output, returns true once the snippet has finished
+    pub fn poll(&mut self) -> bool {
+        if self.finished.is_some() {
+            return true;
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Line(stderr, text)) => self.lines.push(OutputLine { stderr, text }),
+                Ok(Message::Closed) => self.open_streams -= 1,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.open_streams = 0;
+                    break;
+                }
+            }
+        }
+        // done when the process exited and printed everything
+        if let Ok(Some(status)) = self.child.try_wait() {
+            if self.open_streams == 0 || self.cancelled {
+                self.finish(status);
+            }
+        }
+        self.finished.is_some()
+    }
+
+    fn finish(&mut self, status: ExitStatus) {
+        self.finished = Some((status.code(), self.started.elapsed()));
+    }
+
+    pub fn cancel(&mut self) {
+        if self.finished.is_some() {
+            return;
+        }
+        self.cancelled = true;
+        #[cfg(unix)]
+        {
+            let _ = Command::new("kill")
+                .args(["-TERM", "--", &format!("-{}", self.child.id())])
+                .stderr(Stdio::null())
+                .status();
+        }
+        let _ = self.child.kill();
+        if let Ok(status) = self.child.wait() {
+            self.finish(status);
+        }
+    }
+
+    // `exit 0 in 1.20s`, or how long it has been running
+    pub fn summary(&self) -> String {
+        let seconds = self.elapsed().as_secs_f64();
+        match (self.finished, self.cancelled) {
+            (None, _) => format!("running for {:.1}s", seconds),
+            (Some(_), true) => format!("cancelled after {:.2}s", seconds),
+            (Some((Some(code), _)), false) => format!("exit {} in {:.2}s", code, seconds),
+            (Some((None, _)), false) => format!("killed by a signal after {:.2}s", seconds)
+        }
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use super::{command_line, runners, Run};
+
+    fn wait(run: &mut Run) {
+        for _ in 0..500 {
+            if run.poll() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("run did not finish");
+    }
+
+    #[test]
+    fn runners_from_config() {
+        let overrides = BTreeMap::from([
+            (String::from("Python"), String::from("python3.12 -")),
+            (String::from("lua"), String::from("none")),
+            (String::from("node"), String::from("deno run -")),
+        ]);
+        let runners = runners(&overrides);
+        assert_eq!(runners.get("python").map(String::as_str), Some("python3.12 -"));
+        assert_eq!(runners.get("bash").map(String::as_str), Some("bash -s"));
+        assert!(!runners.contains_key("lua"));
+        assert_eq!(runners.get("javascript").map(String::as_str), Some("deno run -"));
+        assert!(!runners.contains_key("py"));
+        assert_eq!(command_line("bash -c {}", "echo hi"), (vec![String::from("bash"), String::from("-c"), String::from("echo hi")], None));
+        assert_eq!(command_line("python3 -", "print(1)").1.as_deref(), Some("print(1)"));
+    }
+
+    #[test]
+    fn captures_output_and_status() {
+        let mut run = Run::start("sh -s", "echo out\necho err >&2\nexit 3").unwrap();
+        wait(&mut run);
+        let lines: Vec<(bool, &str)> = run.lines.iter().map(|line| (line.stderr, line.text.as_str())).collect();
+        assert!(lines.contains(&(false, "out")) && lines.contains(&(true, "err")));
+        assert_eq!(run.finished.map(|(code, _)| code), Some(Some(3)));
+        assert!(run.summary().starts_with("exit 3 in "));
+    }
+
+    #[test]
+    fn cancels_long_runs() {
+        let mut run = Run::start("sh -s", "sleep 5 & wait").unwrap();
+        assert!(!run.poll());
+        run.cancel();
+        assert!(run.poll());
+        assert!(run.elapsed() < Duration::from_secs(5));
+        assert!(run.summary().starts_with("cancelled"));
+    }
+}
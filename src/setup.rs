@@ -0,0 +1,369 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use unicode_width::UnicodeWidthStr;
+use walkdir::WalkDir;
+
+use crate::config::{expand_home, SyncConfig};
+use crate::git::{git_add_remote, git_commit_all, git_current_branch, git_init, git_remote_url};
+use crate::input::TextCursor;
+use crate::state::ensure_ignored;
+
+const DEFAULT_PATH: &str = "~/snippets";
+const DEFAULT_BRANCH: &str = "main";
+const REMOTE_NAME: &str = "origin";
+
+// what is found at the path the user typed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    Missing,
+    NotADirectory,
+    Directory,
+    Repository { snippets: usize, remote: Option<String>, branch: Option<String> },
+}
+
+pub fn probe(path: &str) -> Probe {
+    let dir = Path::new(path);
+    if !dir.exists() {
+        return Probe::Missing;
+    }
+    if !dir.is_dir() {
+        return Probe::NotADirectory;
+    }
+    if !dir.join(".git").exists() {
+        return Probe::Directory;
+    }
+    let snippets = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().and_then(|ext| ext.to_str()) == Some("md"))
+        .count();
+    Probe::Repository { snippets, remote: git_remote_url(path, REMOTE_NAME), branch: git_current_branch(path) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Task {
+    CreateDirectory,
+    InitRepository,
+    InitialCommit,
+    AddRemote(String),
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Task::CreateDirectory => write!(f, "Create the directory"),
+            Task::InitRepository => write!(f, "Initialise a git repository on branch {}", DEFAULT_BRANCH),
+            Task::InitialCommit => write!(f, "Add a .gitignore and make the first commit"),
+            Task::AddRemote(url) => write!(f, "Add the remote {} -> {}", REMOTE_NAME, url),
+        }
+    }
+}
+
+// the steps turning `probe` into a usable snippet repository
+pub fn plan(probe: &Probe, remote: &str) -> Vec<Task> {
+    let mut tasks = match probe {
+        Probe::Missing => vec![Task::CreateDirectory, Task::InitRepository, Task::InitialCommit],
+        Probe::Directory => vec![Task::InitRepository, Task::InitialCommit],
+        Probe::Repository { .. } | Probe::NotADirectory => vec![],
+    };
+    let has_remote = matches!(probe, Probe::Repository { remote: Some(_), .. });
+    if !remote.trim().is_empty() && !has_remote {
+        tasks.push(Task::AddRemote(String::from(remote.trim())));
+    }
+    tasks
+}
+
+pub fn run_task(path: &str, task: &Task) -> Result<(), String> {
+    match task {
+        Task::CreateDirectory => fs::create_dir_all(path).map_err(|err| format!("cannot create {}: {}", path, err)),
+        Task::InitRepository => git_init(path, DEFAULT_BRANCH),
+        Task::InitialCommit => {
+            ensure_ignored(path).map_err(|err| format!("cannot write .gitignore: {}", err))?;
+            git_commit_all(path, "first commit")
+        }
+        Task::AddRemote(url) => git_add_remote(path, REMOTE_NAME, url),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Step {
+    Path,
+    Remote,
+    Review,
+}
+
+// the repository chosen in the wizard and how to sync it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupResult {
+    pub repository: String,
+    pub sync: SyncConfig,
+}
+
+enum Flow {
+    Continue,
+    Cancel,
+    Done(SetupResult),
+}
+
+struct Setup {
+    step: Step,
+    path: String,
+    remote: String,
+    cursor: TextCursor,
+    probe: Probe,
+    error: Option<String>,
+}
+
+impl Setup {
+    fn new() -> Self {
+        Setup {
+            step: Step::Path,
+            path: String::from(DEFAULT_PATH),
+            remote: String::new(),
+            cursor: TextCursor::end(),
+            probe: Probe::Missing,
+            error: None,
+        }
+    }
+
+    fn field(&mut self) -> Option<&mut String> {
+        match self.step {
+            Step::Path => Some(&mut self.path),
+            Step::Remote => Some(&mut self.remote),
+            Step::Review => None,
+        }
+    }
+
+    fn go_to(&mut self, step: Step) {
+        self.step = step;
+        self.cursor = TextCursor::end();
+        self.error = None;
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent) -> Flow {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Flow::Cancel;
+        }
+        match (self.step, key.code) {
+            (Step::Path, KeyCode::Esc) => return Flow::Cancel,
+            (Step::Path, KeyCode::Enter) => {
+                let path = expand_home(self.path.trim());
+                self.probe = probe(&path);
+                match &self.probe {
+                    _ if self.path.trim().is_empty() => self.error = Some(String::from("Enter a directory")),
+                    Probe::NotADirectory => self.error = Some(format!("{} is a file, not a directory", path)),
+                    // an existing remote needs no question
+                    Probe::Repository { remote: Some(_), .. } => self.go_to(Step::Review),
+                    _ => self.go_to(Step::Remote),
+                }
+            }
+            (Step::Remote, KeyCode::Esc) => self.go_to(Step::Path),
+            (Step::Remote, KeyCode::Enter) => self.go_to(Step::Review),
+            (Step::Review, KeyCode::Esc) => match self.probe {
+                Probe::Repository { remote: Some(_), .. } => self.go_to(Step::Path),
+                _ => self.go_to(Step::Remote),
+            },
+            (Step::Review, KeyCode::Enter) => return self.apply(),
+            (_, code) => {
+                let mut cursor = self.cursor;
+                if let Some(field) = self.field() {
+                    match code {
+                        KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => cursor.insert(field, &c.to_string()),
+                        KeyCode::Backspace => cursor.backspace(field),
+                        KeyCode::Delete => cursor.delete(field),
+                        KeyCode::Left => cursor.move_left(field, false),
+                        KeyCode::Right => cursor.move_right(field, false),
+                        KeyCode::Home => cursor.move_home(field, false),
+                        KeyCode::End => cursor.move_end(field, false),
+                        _ => {}
+                    }
+                }
+                self.cursor = cursor;
+            }
+        }
+        Flow::Continue
+    }
+
+    fn paste(&mut self, text: &str) {
+        let mut cursor = self.cursor;
+        if let Some(field) = self.field() {
+            cursor.insert(field, text.trim());
+        }
+        self.cursor = cursor;
+    }
+
+    fn apply(&mut self) -> Flow {
+        let path = expand_home(self.path.trim());
+        for task in plan(&self.probe, &self.remote) {
+            // every task can be run again after fixing what made it fail
+            if let Err(err) = run_task(&path, &task) {
+                self.error = Some(err);
+                return Flow::Continue;
+            }
+        }
+        let (remote, branch) = match probe(&path) {
+            Probe::Repository { remote, branch, .. } => (remote, branch),
+            _ => (None, None)
+        };
+        Flow::Done(SetupResult {
+            repository: String::from(self.path.trim()),
+            sync: SyncConfig {
+                enabled: remote.is_some(),
+                branch: branch.unwrap_or_else(|| String::from(DEFAULT_BRANCH)),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, setup: &Setup) {
+    let size = f.size();
+    let width = size.width.min(76);
+    let height = size.height.min(16);
+    let area = Rect::new(size.x + (size.width - width) / 2, size.y + (size.height - height) / 2, width, height);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(" Set up your snippet library ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(2), Constraint::Length(1)].as_ref())
+        .split(inner);
+
+    let (intro, label) = match setup.step {
+        Step::Path => ("Where should your snippets live? An existing snippet repository is adopted as it is, anything else is created for you.", "Directory"),
+        Step::Remote => ("Optionally enter a remote repository URL to sync with, e.g. https://github.com/you/snippets.git. Leave it empty to keep the snippets local.", "Remote URL"),
+        Step::Review => ("The following will be done:", ""),
+    };
+    f.render_widget(Paragraph::new(intro).wrap(Wrap { trim: true }), chunks[0]);
+
+    match setup.step {
+        Step::Review => {
+            let path = expand_home(setup.path.trim());
+            let tasks = plan(&setup.probe, &setup.remote);
+            let mut lines: Vec<Spans> = tasks.iter().map(|task| Spans::from(format!("  - {}", task))).collect();
+            if let Probe::Repository { snippets, remote, .. } = &setup.probe {
+                lines.insert(0, Spans::from(format!("  - Adopt the repository at {} with {} snippets", path, snippets)));
+                if let Some(remote) = remote {
+                    lines.push(Spans::from(format!("  - Sync with {} ({})", REMOTE_NAME, remote)));
+                }
+            } else {
+                lines.insert(0, Spans::from(format!("  in {}", path)));
+            }
+            f.render_widget(Paragraph::new(lines), chunks[1]);
+        }
+        _ => {
+            let text = match setup.step {
+                Step::Path => setup.path.as_str(),
+                _ => setup.remote.as_str()
+            };
+            let input = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL).title(label).border_style(Style::default().fg(Color::Yellow)));
+            let input_area = Rect::new(chunks[1].x, chunks[1].y, chunks[1].width, 3.min(chunks[1].height));
+            f.render_widget(input, input_area);
+            let mut cursor = setup.cursor;
+            cursor.clamp(text);
+            let prefix: String = text.chars().take(cursor.position).collect();
+            f.set_cursor(input_area.x + 1 + prefix.width() as u16, input_area.y + 1);
+        }
+    }
+
+    if let Some(err) = &setup.error {
+        f.render_widget(Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red)).wrap(Wrap { trim: true }), chunks[2]);
+    }
+    let hints = match setup.step {
+        Step::Path => " enter  continue   esc  quit",
+        Step::Remote => " enter  continue   esc  back",
+        Step::Review => " enter  set up   esc  back",
+    };
+    f.render_widget(Paragraph::new(Span::styled(hints, Style::default().add_modifier(Modifier::DIM))), chunks[3]);
+}
+
+// asks for the snippet repository and prepares it, `None` when the user quits
+pub fn run<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<Option<SetupResult>> {
+    let mut setup = Setup::new();
+    loop {
+        terminal.draw(|f| draw(f, &setup))?;
+        let flow = match event::read()? {
+            Event::Key(key) => setup.handle_key(&key),
+            Event::Paste(text) => {
+                setup.paste(&text);
+                Flow::Continue
+            }
+            _ => Flow::Continue
+        };
+        match flow {
+            Flow::Continue => {}
+            Flow::Cancel => return Ok(None),
+            Flow::Done(result) => return Ok(Some(result)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use super::{plan, probe, run_task, Probe, Task};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = Path::new("./target/temp/setup").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.parent().unwrap()).unwrap();
+        dir
+    }
+
+    #[test]
+    fn plans_by_what_exists() {
+        assert_eq!(plan(&Probe::Missing, ""), vec![Task::CreateDirectory, Task::InitRepository, Task::InitialCommit]);
+        assert_eq!(plan(&Probe::Directory, " https://example.com/s.git "), vec![
+            Task::InitRepository, Task::InitialCommit, Task::AddRemote(String::from("https://example.com/s.git"))
+        ]);
+        let adopted = Probe::Repository { snippets: 3, remote: Some(String::from("url")), branch: None };
+        assert!(plan(&adopted, "other").is_empty());
+    }
+
+    #[test]
+    fn probes_paths() {
+        let dir = temp_dir("probe");
+        let path = dir.to_str().unwrap();
+        assert_eq!(probe(path), Probe::Missing);
+        fs::write(&dir, "").unwrap();
+        assert_eq!(probe(path), Probe::NotADirectory);
+        fs::remove_file(&dir).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("python")).unwrap();
+        fs::write(dir.join("python").join("hello.md"), "{}").unwrap();
+        assert!(matches!(probe(path), Probe::Repository { snippets: 1, .. }));
+    }
+
+    #[test]
+    fn creates_repository() {
+        let dir = temp_dir("create");
+        let path = dir.to_str().unwrap();
+        run_task(path, &Task::CreateDirectory).unwrap();
+        run_task(path, &Task::InitRepository).unwrap();
+        run_task(path, &Task::AddRemote(String::from("https://example.com/s.git"))).unwrap();
+        assert_eq!(probe(path), Probe::Repository {
+            snippets: 0,
+            remote: Some(String::from("https://example.com/s.git")),
+            branch: Some(String::from("main")),
+        });
+        assert!(run_task(path, &Task::AddRemote(String::from("again"))).is_err());
+    }
+}
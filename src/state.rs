@@ -0,0 +1,48 @@
+use std::fs;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+type EResult<T> = Result<T, Box<dyn Error>>;
+
+// local state lives inside the snippet repository but is never committed
+pub const STATE_DIR: &str = ".snippet_state";
+
+// creates the state directory, use `state_path` to only read
+pub fn state_file(base_path: &str, name: &str) -> EResult<PathBuf> {
+    let dir = Path::new(base_path).join(STATE_DIR);
+    fs::create_dir_all(&dir)?;
+    ensure_ignored(base_path)?;
+    Ok(dir.join(name))
+}
+
+pub fn state_path(base_path: &str, name: &str) -> PathBuf {
+    Path::new(base_path).join(STATE_DIR).join(name)
+}
+
+pub fn ensure_ignored(base_path: &str) -> EResult<()> {
+    let gitignore = Path::new(base_path).join(".gitignore");
+    let entry = format!("/{}/", STATE_DIR);
+    let mut contents = fs::read_to_string(&gitignore).unwrap_or_default();
+    if contents.lines().any(|line| line.trim() == entry) {
+        return Ok(());
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&entry);
+    contents.push('\n');
+    fs::write(gitignore, contents)?;
+    Ok(())
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let buf = fs::read(path).ok()?;
+    serde_json::from_slice::<T>(&buf).ok()
+}
+
+pub fn save<T: Serialize>(path: &Path, value: &T) -> EResult<()> {
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
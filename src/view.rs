@@ -4,19 +4,33 @@ use tui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, List, ListItem, ListState, Paragraph, BorderType, Borders, Wrap},
+    layout::Rect,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, BorderType, Borders, Wrap},
     Frame, Terminal,
 };
 use unicode_width::UnicodeWidthStr;
+use crossterm::event::{KeyCode, KeyEvent};
 
-use crate::note::Note;
-use crate::key::InputMode;
+use crate::note::{Note, Problem};
+use crate::key::{Action, InputMode};
+use crate::input::TextCursor;
+use crate::clipboard::ClipHistory;
+use crate::config::{Library, Settings, SyncConfig, Theme, ALL_PROFILES, DEFAULT_PROFILE};
+use crate::keymap::Keymap;
+use crate::vim::{self, VimMode, VimState};
+use crate::palette::{palette_entries, PaletteState};
+use crate::pick::PickerState;
+use crate::placeholder::{FillForm, PlaceholderMemory};
+use crate::runner::{runners, Run};
 
 use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 pub struct StatefulList<T> {
     selected_num: Option<usize>,
     state: ListState,
+    offset: usize,
     pub items: Vec<T>
 }
 
@@ -26,20 +40,22 @@ impl<T> StatefulList<T> {
     }
 
     pub fn set_selected_num(&mut self, num: usize) {
-        self.selected_num = Some(num as usize);
-        self.state.select(Some(num as usize));
+        self.selected_num = Some(num);
+        self.state.select(Some(num));
     }
 
     pub fn with_items(items: Vec<T>) -> StatefulList<T> {
         StatefulList {
             selected_num: None,
             state: ListState::default(),
+            offset: 0,
             items
         }
     }
 
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
+    // the item after the selected one, wrapping around
+    pub fn next_index(&self) -> usize {
+        match self.state.selected() {
             Some(i) => {
                 match i >= self.items.len() - 1 {
                     true  => 0,
@@ -47,12 +63,11 @@ impl<T> StatefulList<T> {
                 }
             }
             None => 0
-        };
-        self.set_selected_num(i);
+        }
     }
 
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
+    pub fn previous_index(&self) -> usize {
+        match self.state.selected() {
             Some(i) => {
                 match i == 0 {
                     true  => self.items.len() - 1,
@@ -60,8 +75,7 @@ impl<T> StatefulList<T> {
                 }
             }
             None => 0
-        };
-        self.set_selected_num(i);
+        }
     }
 
     pub fn delete(&mut self, index: usize) {
@@ -69,38 +83,604 @@ impl<T> StatefulList<T> {
     }
 
     pub fn unselect(&mut self) {
-        self.state.select(None)
+        self.selected_num = None;
+        self.state.select(None);
+        self.offset = 0;
+    }
+
+    // mirror the scrolling of the list widget so rows can be mapped to items
+    fn update_offset(&mut self, height: usize) {
+        if let Some(selected) = self.state.selected() {
+            if selected >= self.offset + height {
+                self.offset = selected + 1 - height;
+            } else if selected < self.offset {
+                self.offset = selected;
+            }
+        }
+    }
+
+    pub fn item_at(&self, row: usize) -> Option<usize> {
+        let index = self.offset + row;
+        match index < self.items.len() {
+            true => Some(index),
+            false => None
+        }
+    }
+}
+
+// text areas of the panes, kept for mapping mouse events
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PaneAreas {
+    pub search: Rect,
+    pub list: Rect,
+    pub title: Rect,
+    pub language: Rect,
+    pub contents: Rect,
+    pub screen: Rect,
+}
+
+// where the user goes when leaving the current note
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Leave {
+    Select(usize),
+    NewNote,
+    // `None` merges all libraries
+    Library(Option<usize>),
+    Quit,
+}
+
+// what a confirm dialog does once a choice is picked
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Confirmed {
+    Cancel,
+    DeleteNote,
+    ClearField,
+    OverwriteNote(Option<Leave>),
+    RunNote,
+    SaveAndLeave(Leave),
+    DiscardAndLeave(Leave),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Choice {
+    pub label: &'static str,
+    pub key: char,
+    pub outcome: Confirmed,
+}
+
+impl Choice {
+    pub fn new(label: &'static str, key: char, outcome: Confirmed) -> Self {
+        Choice { label, key, outcome }
+    }
+
+    fn button(&self) -> String {
+        format!(" [{}] {} ", self.key, self.label)
+    }
+}
+
+// a modal question answered with the keyboard or by clicking a button
+#[derive(Debug, Clone)]
+pub struct ConfirmDialog {
+    pub title: String,
+    pub message: String,
+    pub choices: Vec<Choice>,
+    pub selected: usize,
+}
+
+impl ConfirmDialog {
+    // the cancel choice is selected first so a stray enter does no harm
+    pub fn new(title: &str, message: &str, choices: Vec<Choice>) -> Self {
+        let selected = choices.iter().position(|c| c.outcome == Confirmed::Cancel).unwrap_or(0);
+        ConfirmDialog { title: String::from(title), message: String::from(message), choices, selected }
+    }
+
+    // a destructive action with a single confirming choice
+    pub fn destructive(title: &str, message: &str, label: &'static str, outcome: Confirmed) -> Self {
+        ConfirmDialog::new(title, message, vec![Choice::new(label, 'y', outcome), Choice::new("Cancel", 'n', Confirmed::Cancel)])
+    }
+
+    // `None` while the dialog stays open
+    pub fn handle_key(&mut self, key: &KeyEvent) -> Option<Confirmed> {
+        let count = self.choices.len();
+        match key.code {
+            KeyCode::Left | KeyCode::BackTab => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Right | KeyCode::Tab => self.selected = (self.selected + 1) % count,
+            KeyCode::Enter => return Some(self.choices[self.selected].outcome),
+            KeyCode::Esc => return Some(Confirmed::Cancel),
+            KeyCode::Char(c) => {
+                let c = c.to_ascii_lowercase();
+                return self.choices.iter().find(|choice| choice.key == c).map(|choice| choice.outcome);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn area(&self, screen: Rect) -> Rect {
+        let buttons: usize = self.choices.iter().map(|c| c.button().width() + 1).sum();
+        let text = self.message.lines().map(|line| line.width()).max().unwrap_or(0);
+        let width = (text.max(buttons).max(self.title.width()) as u16 + 4).min(screen.width);
+        let height = (self.message.lines().count() as u16 + 4).min(screen.height);
+        Rect::new(
+            screen.x + (screen.width - width) / 2,
+            screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        )
+    }
+
+    // screen areas of the buttons, in the order of `choices`
+    pub fn buttons(&self, screen: Rect) -> Vec<Rect> {
+        let area = self.area(screen);
+        let row = area.y + area.height.saturating_sub(2);
+        let mut x = area.x + 2;
+        self.choices.iter().map(|choice| {
+            let width = (choice.button().width() as u16).min((area.x + area.width).saturating_sub(x));
+            let button = Rect::new(x, row, width, 1);
+            x += width + 1;
+            button
+        }).collect()
     }
 }
 
+// overlays drawn on top of the panes, they take all keys while open
+pub enum Popup {
+    ClipHistory(usize),
+    Palette(PaletteState),
+    Help(u16),
+    Confirm(ConfirmDialog),
+    Libraries(usize),
+    Fill(FillForm),
+    // lines scrolled back from the end of the output, 0 follows it
+    Output(u16),
+    // 0 is a blank snippet, the templates follow
+    Templates(usize),
+    Problems(usize),
+}
+
 pub struct App {
-    pub input: String,
     pub input_mode: InputMode,
     pub list: StatefulList<Note>,
+    pub cursor: TextCursor,
+    pub areas: PaneAreas,
+    pub content_scroll: u16,
+    pub status: String,
+    pub settings: Settings,
+    pub theme: Theme,
+    pub popup: Option<Popup>,
+    pub clip_history: ClipHistory,
+    pub placeholders: PlaceholderMemory,
+    // interpreter command for each lowercase language
+    pub runners: BTreeMap<String, String>,
+    pub run: Option<Run>,
+    // the templates offered for a new snippet, by name
+    pub templates: Vec<(String, Note)>,
+    // files of the listed libraries that could not be loaded
+    pub problems: Vec<Problem>,
+    pub keymap: Keymap,
+    pub vim: VimState,
+    // the note as it is on disk, `note` is compared to it
+    pub saved: Note,
+    pub libraries: Vec<Library>,
+    // `None` lists the snippets of all libraries
+    pub active: Option<usize>,
 }
 
 impl Default for App {
     fn default() -> App {
         App {
-            input: String::new(),
             input_mode: InputMode::Normal,
-            list: StatefulList::with_items(vec![])
+            list: StatefulList::with_items(vec![]),
+            cursor: TextCursor::default(),
+            areas: PaneAreas::default(),
+            content_scroll: 0,
+            status: String::new(),
+            settings: Settings::default(),
+            theme: Theme::default(),
+            popup: None,
+            clip_history: ClipHistory::new(0),
+            placeholders: PlaceholderMemory::default(),
+            runners: runners(&BTreeMap::new()),
+            run: None,
+            templates: vec![],
+            problems: vec![],
+            keymap: Keymap::default(),
+            vim: VimState::default(),
+            saved: Note::new("", "", "", ""),
+            libraries: vec![Library { name: String::from(DEFAULT_PROFILE), path: String::new(), sync: SyncConfig::default() }],
+            active: Some(0)
         }
     }
 }
 
+impl App {
+    // switching fields puts the cursor behind the text of the new field
+    pub fn set_mode(&mut self, mode: InputMode) {
+        self.input_mode = mode;
+        self.cursor = TextCursor::end();
+        self.vim.mode = VimMode::Normal;
+    }
+
+    // a note that was never saved has no title yet, it counts once it has contents, e.g. from a template
+    pub fn is_modified(&self, note: &Note) -> bool {
+        *note != self.saved || (self.saved.title.is_empty() && !note.contents.trim().is_empty())
+    }
+
+    // the libraries whose snippets are listed
+    pub fn active_libraries(&self) -> Vec<&Library> {
+        match self.active {
+            Some(index) => vec![&self.libraries[index]],
+            None => self.libraries.iter().collect()
+        }
+    }
+
+    // new snippets go to the first listed library
+    pub fn home(&self) -> &Library {
+        self.active_libraries()[0]
+    }
+
+    // the library holding `note`, judged by its path
+    pub fn library_of(&self, note: &Note) -> &Library {
+        self.active_libraries()
+            .into_iter()
+            .filter(|library| !library.path.is_empty() && Path::new(&note.file_path).starts_with(&library.path))
+            .max_by_key(|library| library.path.len())
+            .unwrap_or_else(|| self.home())
+    }
+
+    // the switcher choices, with `None` for all libraries
+    pub fn library_choices(&self) -> Vec<Option<usize>> {
+        let mut choices: Vec<Option<usize>> = (0..self.libraries.len()).map(Some).collect();
+        if self.libraries.len() > 1 {
+            choices.push(None);
+        }
+        choices
+    }
+
+    pub fn library_title(&self) -> String {
+        match self.active {
+            Some(index) => self.libraries[index].name.clone(),
+            None => String::from(ALL_PROFILES)
+        }
+    }
+}
+
+// split text into lines, highlighting the selected char range
+fn highlighted_lines(text: &str, selection: Option<(usize, usize)>) -> Vec<Spans<'_>> {
+    let mut index = 0;
+    text.split('\n').map(|line| {
+        let len = line.chars().count();
+        let spans = match selection {
+            Some((start, end)) => {
+                let start = start.saturating_sub(index).min(len);
+                let end = end.saturating_sub(index).min(len);
+                let part = |from: usize, to: usize| -> String { line.chars().skip(from).take(to - from).collect() };
+                Spans::from(vec![
+                    Span::raw(part(0, start)),
+                    Span::styled(part(start, end), Style::default().add_modifier(Modifier::REVERSED)),
+                    Span::raw(part(end, len)),
+                ])
+            }
+            None => Spans::from(Span::raw(line))
+        };
+        index += len + 1;
+        spans
+    }).collect()
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2)
+        ].as_ref())
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2)
+        ].as_ref())
+        .split(vertical[1])[1]
+}
+
+fn popup_block(title: &str) -> Block<'_> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_alignment(Alignment::Center)
+}
+
+fn render_popup<B: Backend>(f: &mut Frame<B>, app: &App, popup: &Popup) {
+    match popup {
+        Popup::ClipHistory(selected) => {
+            let area = centered_rect(60, 50, f.size());
+            let items: Vec<ListItem> = app.clip_history.entries()
+                .iter()
+                .map(|text| {
+                    let first_line = text.lines().next().unwrap_or("");
+                    let lines = text.lines().count();
+                    let label = match lines > 1 {
+                        true => format!("  {}  (+{} lines)", first_line, lines - 1),
+                        false => format!("  {}", first_line)
+                    };
+                    ListItem::new(label)
+                })
+                .collect();
+            let list = List::new(items)
+                .block(popup_block("Clipboard history"))
+                .highlight_style(Style::default().bg(app.theme.accent).add_modifier(Modifier::BOLD));
+            let mut state = ListState::default();
+            state.select(Some(*selected));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Popup::Help(scroll) => {
+            let area = centered_rect(60, 70, f.size());
+            let line = |key: String, description: &'static str| Spans::from(vec![
+                Span::styled(format!("  {:<16}", key), Style::default().fg(app.theme.accent)),
+                Span::raw(description),
+            ]);
+            let mut lines: Vec<Spans> = app.keymap.bindings_for(app.input_mode)
+                .into_iter()
+                .map(|(key, action)| line(key.to_string(), action.description()))
+                .collect();
+            if app.settings.vim_mode {
+                lines.push(Spans::from(""));
+                lines.push(Spans::from(Span::styled("Vim keys in the contents", Style::default().add_modifier(Modifier::BOLD))));
+                lines.extend(vim::help().into_iter().map(|(keys, description)| line(keys, description)));
+            }
+            let title = format!("Keys in {} mode", app.input_mode);
+            let help = Paragraph::new(lines)
+                .block(popup_block(&title))
+                .scroll((*scroll, 0));
+            f.render_widget(Clear, area);
+            f.render_widget(help, area);
+        }
+        Popup::Confirm(dialog) => {
+            let area = dialog.area(f.size());
+            let block = popup_block(&dialog.title).border_style(Style::default().fg(app.theme.accent));
+            let text = block.inner(area);
+            let text = Rect::new(text.x + 1, text.y, text.width.saturating_sub(2), text.height);
+            f.render_widget(Clear, area);
+            f.render_widget(block, area);
+            f.render_widget(Paragraph::new(dialog.message.as_str()), text);
+            for (i, (choice, button)) in dialog.choices.iter().zip(dialog.buttons(f.size())).enumerate() {
+                let style = match i == dialog.selected {
+                    true => Style::default().bg(app.theme.accent).fg(Color::Black).add_modifier(Modifier::BOLD),
+                    false => Style::default().add_modifier(Modifier::REVERSED)
+                };
+                f.render_widget(Paragraph::new(Span::styled(choice.button(), style)), button);
+            }
+        }
+        Popup::Libraries(selected) => {
+            let area = centered_rect(60, 40, f.size());
+            let items: Vec<ListItem> = app.library_choices()
+                .into_iter()
+                .map(|choice| {
+                    let current = match choice == app.active {
+                        true => "* ",
+                        false => "  "
+                    };
+                    let (name, path) = match choice {
+                        Some(index) => (app.libraries[index].name.as_str(), app.libraries[index].path.as_str()),
+                        None => (ALL_PROFILES, "every library above")
+                    };
+                    ListItem::new(Spans::from(vec![
+                        Span::raw(format!("{}{:<16}", current, name)),
+                        Span::styled(path, Style::default().fg(app.theme.muted)),
+                    ]))
+                })
+                .collect();
+            let list = List::new(items)
+                .block(popup_block("Snippet libraries"))
+                .highlight_style(Style::default().bg(app.theme.accent).add_modifier(Modifier::BOLD));
+            let mut state = ListState::default();
+            state.select(Some(*selected));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Popup::Fill(form) => render_fill_form(f, form, &app.theme),
+        Popup::Problems(selected) => {
+            let area = centered_rect(80, 60, f.size());
+            let items: Vec<ListItem> = app.problems.iter()
+                .map(|problem| ListItem::new(vec![
+                    Spans::from(Span::raw(format!("  {}", problem.path))),
+                    Spans::from(Span::styled(format!("    {}", problem.message), Style::default().fg(app.theme.muted))),
+                ]))
+                .collect();
+            let list = List::new(items)
+                .block(popup_block("Problems: enter opens the file as text, esc closes"))
+                .highlight_style(Style::default().bg(app.theme.accent).add_modifier(Modifier::BOLD));
+            let mut state = ListState::default();
+            state.select(Some(*selected));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Popup::Templates(selected) => {
+            let area = centered_rect(60, 40, f.size());
+            let mut items = vec![ListItem::new("  Blank snippet")];
+            items.extend(app.templates.iter().map(|(name, template)| ListItem::new(Spans::from(vec![
+                Span::raw(format!("  {:<24}", name)),
+                Span::styled(template.language.as_str(), Style::default().fg(app.theme.muted)),
+            ]))));
+            let list = List::new(items)
+                .block(popup_block("New snippet"))
+                .highlight_style(Style::default().bg(app.theme.accent).add_modifier(Modifier::BOLD));
+            let mut state = ListState::default();
+            state.select(Some(*selected));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(list, area, &mut state);
+        }
+        Popup::Output(back) => {
+            let run = match &app.run {
+                Some(run) => run,
+                None => return
+            };
+            let area = centered_rect(80, 70, f.size());
+            let title = format!("Output: {}", run.command);
+            let block = popup_block(&title);
+            let inner = block.inner(area);
+            let height = inner.height.saturating_sub(1) as usize;
+            let end = run.lines.len().saturating_sub(*back as usize);
+            let lines: Vec<Spans> = run.lines[end.saturating_sub(height)..end]
+                .iter()
+                .map(|line| match line.stderr {
+                    true => Spans::from(Span::styled(line.text.as_str(), Style::default().fg(Color::Red))),
+                    false => Spans::from(Span::raw(line.text.as_str()))
+                })
+                .collect();
+            let hint = match run.running() {
+                true => "ctrl-c cancel",
+                false => "esc close"
+            };
+            let status = Spans::from(vec![
+                Span::styled(format!(" {} ", run.summary()), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+                Span::styled(format!(" {}  up/down scroll", hint), Style::default().fg(app.theme.muted)),
+            ]);
+            f.render_widget(Clear, area);
+            f.render_widget(block, area);
+            f.render_widget(Paragraph::new(lines), Rect::new(inner.x, inner.y, inner.width, height as u16));
+            f.render_widget(Paragraph::new(status), Rect::new(inner.x, inner.y + height as u16, inner.width, 1));
+        }
+        Popup::Palette(palette) => {
+            let area = centered_rect(60, 60, f.size());
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+                .split(area);
+            let input = Paragraph::new(palette.query.as_str())
+                .block(popup_block("Command palette"));
+            let items: Vec<ListItem> = palette_entries(&app.keymap, app.input_mode, app.settings.vim_mode, &palette.query)
+                .into_iter()
+                .map(|(action, keys)| {
+                    let keys = keys.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(", ");
+                    ListItem::new(Spans::from(vec![
+                        Span::raw(format!("  {:<42}", action.description())),
+                        Span::styled(keys, Style::default().fg(app.theme.muted)),
+                    ]))
+                })
+                .collect();
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded))
+                .highlight_style(Style::default().bg(app.theme.accent).add_modifier(Modifier::BOLD));
+            let mut state = ListState::default();
+            state.select(Some(palette.selected));
+            f.render_widget(Clear, area);
+            f.render_widget(input, chunks[0]);
+            f.render_stateful_widget(list, chunks[1], &mut state);
+            f.set_cursor(chunks[0].x + palette.query.width() as u16 + 1, chunks[0].y + 1);
+        }
+    }
+}
+
+// one row per placeholder, shared by the full-screen interface and `pick`
+fn render_fill_form<B: Backend>(f: &mut Frame<B>, form: &FillForm, theme: &Theme) {
+    let screen = f.size();
+    let height = (form.fields.len() as u16 + 4).min(screen.height);
+    let area = centered_rect(60, 100, screen);
+    let area = Rect::new(area.x, screen.height.saturating_sub(height) / 2, area.width, height);
+    let width = form.fields.iter().map(|field| field.name.width()).max().unwrap_or(0) + 2;
+    let lines: Vec<Spans> = form.fields.iter().zip(&form.values).enumerate()
+        .map(|(i, (field, value))| {
+            let style = match i == form.selected {
+                true => Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+                false => Style::default()
+            };
+            let choices = match field.choices.is_empty() {
+                true => String::new(),
+                false => format!("  ({})", field.choices.join(", "))
+            };
+            Spans::from(vec![
+                Span::styled(format!(" {:>width$} ", format!("{}:", field.name), width = width), style),
+                Span::raw(value.as_str()),
+                Span::styled(choices, Style::default().fg(theme.muted)),
+            ])
+        })
+        .collect();
+    let block = popup_block("Fill in placeholders").border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+    f.render_widget(Paragraph::new(lines), Rect::new(inner.x, inner.y, inner.width, inner.height.saturating_sub(1)));
+    let hint = Span::styled(" enter done  tab next  left/right choice  esc cancel", Style::default().fg(theme.muted));
+    f.render_widget(Paragraph::new(hint), Rect::new(inner.x, inner.y + inner.height.saturating_sub(1), inner.width, 1));
+    let x = inner.x + width as u16 + 2 + form.values[form.selected].width() as u16;
+    f.set_cursor(x.min(inner.x + inner.width.saturating_sub(1)), inner.y + form.selected as u16);
+}
+
+// the focused pane gets a thick border in the accent color
+fn pane_block(title: String, focused: bool, theme: &Theme) -> Block<'static> {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_alignment(Alignment::Center);
+    match focused {
+        true => block.border_type(BorderType::Thick).border_style(Style::default().fg(theme.accent)),
+        false => block
+    }
+}
+
+// the keys most worth knowing in each mode
+fn hint_actions(mode: InputMode) -> [Action; 3] {
+    match mode {
+        InputMode::Normal => [Action::NewNote, Action::Save, Action::Help],
+        InputMode::EditingSearch => [Action::ApplySearch, Action::FocusList, Action::Help],
+        InputMode::EditingTitle | InputMode::EditingLanguage => [Action::NextField, Action::FocusList, Action::Help],
+        InputMode::EditingCode => [Action::Copy, Action::FocusList, Action::Help],
+    }
+}
+
+fn hint_bar(app: &App) -> Spans<'_> {
+    let mut spans = vec![];
+    // vim's own keys while the contents are in normal mode, they leave room for help only
+    let vim_keys = app.settings.vim_mode && app.input_mode == InputMode::EditingCode && app.vim.mode == VimMode::Normal;
+    if vim_keys {
+        for (keys, description) in vim::hints() {
+            spans.push(Span::styled(format!(" {} ", keys), Style::default().add_modifier(Modifier::REVERSED)));
+            spans.push(Span::raw(format!(" {}  ", description.to_lowercase())));
+        }
+    }
+    for action in hint_actions(app.input_mode).into_iter().filter(|action| !vim_keys || *action == Action::Help) {
+        if let Some(key) = app.keymap.keys_for(app.input_mode, action).first() {
+            spans.push(Span::styled(format!(" {} ", key), Style::default().add_modifier(Modifier::REVERSED)));
+            spans.push(Span::raw(format!(" {}  ", action.name().replace('_', " "))));
+        }
+    }
+    Spans::from(spans)
+}
+
+// display width of the text in front of the cursor on its line
+fn cursor_offset(text: &str, cursor: &TextCursor) -> (u16, u16) {
+    let mut cursor = *cursor;
+    cursor.clamp(text);
+    let (line, col) = cursor.line_col(text);
+    let prefix: String = text.split('\n').nth(line).unwrap_or("").chars().take(col).collect();
+    (prefix.width() as u16, line as u16)
+}
+
 pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, note: &Note, search_text: &str) {
     let size = f.size();
+    app.areas.screen = size;
 
     // Surrounding block
     let block = Block::default();
     f.render_widget(block, size);
 
+    let outer_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1), Constraint::Length(1)].as_ref())
+        .split(size);
+
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .margin(1)
         .constraints([Constraint::Percentage(40),Constraint::Length(2), Constraint::Percentage(58)].as_ref())
-        .split(f.size());
+        .split(outer_chunks[0]);
 
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -109,52 +689,77 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, note: &Note, search_text:
 
     let right_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Length(1), Constraint::Min(1)].as_ref())
+        .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(1)].as_ref())
         .split(chunks[2]);
 
-    let search_input = Paragraph::new(search_text.as_ref())
+    let focused = |mode: InputMode| app.input_mode == mode;
+    let selection = |mode: InputMode| match app.input_mode == mode {
+        true => app.cursor.selection(),
+        false => None
+    };
+
+    let search_input = Paragraph::new(highlighted_lines(search_text, selection(InputMode::EditingSearch)))
         .style(match app.input_mode {
             InputMode::Normal => Style::default(),
-            InputMode::EditingSearch => Style::default().fg(Color::Yellow),
+            InputMode::EditingSearch => Style::default().fg(app.theme.accent),
             _ => Style::default()
         })
-        .block(Block::default().borders(Borders::ALL).title("Search").title_alignment(Alignment::Center));
+        .block(pane_block(String::from("Search"), focused(InputMode::EditingSearch), &app.theme));
     f.render_widget(search_input, left_chunks[0]);
+    app.areas.search = Block::default().borders(Borders::ALL).inner(left_chunks[0]);
     // Iterate through all elements in the `items` app and append some debug text to it.
+    let modified = app.is_modified(note);
     let items: Vec<ListItem> = app.list.items
         .iter()
-        .enumerate()
-        .map(|(_i, m)| {
-            let content = vec![Spans::from(Span::raw(format!("  {}", m.title)))];
+        .map(|m| {
+            let marker = match modified && m.file_path == app.saved.file_path {
+                true => " *",
+                false => ""
+            };
+            // show where each snippet comes from when several libraries are listed
+            let source = match app.active {
+                None => format!("[{}] ", app.library_of(m).name),
+                Some(_) => String::new()
+            };
+            let content = vec![Spans::from(vec![
+                Span::raw("  "),
+                Span::styled(source, Style::default().fg(app.theme.muted)),
+                Span::raw(format!("{}{}", m.title, marker)),
+            ])];
             ListItem::new(content).style(Style::default())//.fg(Color::Black).bg(Color::White))
         })
         .collect();
 
     // Create a List from all list items and highlight the currently selected one
     let items = List::new(items)
-        .block(Block::default()
-        .borders(Borders::ALL)
-        .title("List")
-        .title_alignment(Alignment::Center))
+        .block(pane_block(match app.libraries.len() > 1 {
+            true => format!("List: {}", app.library_title()),
+            false => String::from("List")
+        }, focused(InputMode::Normal), &app.theme))
         .highlight_style(
             Style::default()
-            .bg(Color::Yellow)
+            .bg(app.theme.accent)
             .add_modifier(Modifier::BOLD)
         );
 
+    app.areas.list = Block::default().borders(Borders::ALL).inner(left_chunks[1]);
+    app.list.update_offset(app.areas.list.height as usize);
     f.render_stateful_widget(items, left_chunks[1], &mut app.list.state);
 
-    let title = Paragraph::new(note.title.as_ref())
-        .block(Block::default()
-        .borders(Borders::NONE))
-        .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
+    let mut title_lines = highlighted_lines(&note.title, selection(InputMode::EditingTitle));
+    if modified {
+        title_lines[0].0.push(Span::styled(" [modified]", Style::default().fg(app.theme.muted)));
+    }
+    let title = Paragraph::new(title_lines)
+        .block(pane_block(String::from("Title"), focused(InputMode::EditingTitle), &app.theme))
+        .alignment(Alignment::Left);
     f.render_widget(title, right_chunks[0]);
+    app.areas.title = Block::default().borders(Borders::ALL).inner(right_chunks[0]);
 
-    let language = Paragraph::new(note.language.as_ref())
-        .block(Block::default()
-            .borders(Borders::NONE));
+    let language = Paragraph::new(highlighted_lines(&note.language, selection(InputMode::EditingLanguage)))
+        .block(pane_block(String::from("Language"), focused(InputMode::EditingLanguage), &app.theme));
     f.render_widget(language, right_chunks[1]);
+    app.areas.language = Block::default().borders(Borders::ALL).inner(right_chunks[1]);
 
     let mut replaced_contents = note.contents.clone();
     let re = Regex::new(r"(\n[ \t]\w*)").unwrap();
@@ -163,40 +768,185 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, note: &Note, search_text:
         let end = caps.get(0).unwrap().end();
         replaced_contents.replace_range(start..end, &" ".repeat(end - start));
     }
-    let contents = Paragraph::new(replaced_contents.as_ref())
+    // keep the cursor line visible while editing
+    app.areas.contents = Block::default().borders(Borders::ALL).inner(right_chunks[2]);
+    if app.input_mode == InputMode::EditingCode {
+        let (_, y) = cursor_offset(&note.contents, &app.cursor);
+        let height = app.areas.contents.height.max(1);
+        if y < app.content_scroll {
+            app.content_scroll = y;
+        } else if y >= app.content_scroll + height {
+            app.content_scroll = y + 1 - height;
+        }
+    }
+
+    let contents = Paragraph::new(highlighted_lines(&replaced_contents, selection(InputMode::EditingCode)))
         .style(match app.input_mode {
             InputMode::Normal => Style::default(),
-            InputMode::EditingCode => Style::default().fg(Color::Yellow),
+            InputMode::EditingCode => Style::default().fg(app.theme.accent),
             _ => Style::default()
         })
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title(app.input_mode.to_string())
-            .title_alignment(Alignment::Center))
-        .alignment(Alignment::Left);
+        .block(pane_block(match (app.settings.vim_mode, app.input_mode) {
+            (true, InputMode::EditingCode) => format!("Contents {}", app.vim.mode),
+            _ => String::from("Contents")
+        }, focused(InputMode::EditingCode), &app.theme))
+        .alignment(Alignment::Left)
+        .scroll((app.content_scroll, 0));
      //   .wrap(Wrap { trim: true });
     f.render_widget(contents, right_chunks[2]);
 
+    let status = match &app.vim.command_line {
+        Some(command) => Paragraph::new(format!(":{}", command)),
+        None => Paragraph::new(app.status.as_str()).style(Style::default().fg(app.theme.accent))
+    };
+    f.render_widget(status, outer_chunks[1]);
+    f.render_widget(Paragraph::new(hint_bar(app)), outer_chunks[2]);
+
+    if let Some(popup) = &app.popup {
+        render_popup(f, app, popup);
+        return;
+    }
+    if let Some(command) = &app.vim.command_line {
+        f.set_cursor(outer_chunks[1].x + command.width() as u16 + 1, outer_chunks[1].y);
+        return;
+    }
+
     match app.input_mode {
         InputMode::Normal => {}
         InputMode::EditingSearch => {
+            let (x, _) = cursor_offset(search_text, &app.cursor);
             f.set_cursor(
-                left_chunks[0].x + search_text.width() as u16 + 1,
-                left_chunks[0].y + 1
+                app.areas.search.x + x,
+                app.areas.search.y
             )
         }
         InputMode::EditingTitle => {
+            let (x, _) = cursor_offset(&note.title, &app.cursor);
             f.set_cursor(
-                right_chunks[0].x + note.title.width() as u16,
-                right_chunks[0].y
+                app.areas.title.x + x,
+                app.areas.title.y
             )
         }
         InputMode::EditingLanguage => {
+            let (x, _) = cursor_offset(&note.language, &app.cursor);
+            f.set_cursor(
+                app.areas.language.x + x,
+                app.areas.language.y
+            )
+        }
+        InputMode::EditingCode => {
+            let (x, y) = cursor_offset(&note.contents, &app.cursor);
             f.set_cursor(
-                right_chunks[1].x + note.language.width() as u16,
-                right_chunks[1].y 
+                app.areas.contents.x + x,
+                app.areas.contents.y + y - app.content_scroll
             )
         }
-        _ => {}
     }
 }
+
+// the picker of `pick`: search box and list on the left, preview on the right
+pub fn picker_ui<B: Backend>(f: &mut Frame<B>, picker: &PickerState, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(chunks[1]);
+
+    let matches = picker.matches();
+    let search = Paragraph::new(picker.query.as_str())
+        .style(Style::default().fg(theme.accent))
+        .block(pane_block(format!("Pick a snippet ({}/{})", matches.len(), picker.notes.len()), true, theme));
+    f.render_widget(search, chunks[0]);
+
+    let items: Vec<ListItem> = matches.iter()
+        .map(|i| ListItem::new(format!("  {}", picker.labels[*i])))
+        .collect();
+    let list = List::new(items)
+        .block(pane_block(String::from("List"), false, theme))
+        .highlight_style(Style::default().bg(theme.accent).add_modifier(Modifier::BOLD));
+    let mut state = ListState::default();
+    state.select(match matches.is_empty() {
+        true => None,
+        false => Some(picker.selected)
+    });
+    f.render_stateful_widget(list, panes[0], &mut state);
+
+    let (title, contents) = match matches.get(picker.selected) {
+        Some(i) => (picker.notes[*i].title.clone(), picker.notes[*i].contents.as_str()),
+        None => (String::from("Preview"), "")
+    };
+    let preview = Paragraph::new(highlighted_lines(contents, None))
+        .block(pane_block(title, false, theme));
+    f.render_widget(preview, panes[1]);
+
+    let hints = Spans::from(vec![
+        Span::styled(" enter ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(" pick  "),
+        Span::styled(" up/down ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(" move  "),
+        Span::styled(" esc ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(" cancel  "),
+    ]);
+    f.render_widget(Paragraph::new(hints), chunks[2]);
+    match &picker.form {
+        Some(form) => render_fill_form(f, form, theme),
+        None => f.set_cursor(chunks[0].x + picker.query.width() as u16 + 1, chunks[0].y + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use tui::layout::Rect;
+    use super::{App, ConfirmDialog, Confirmed};
+    use crate::note::Note;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn confirm_defaults_to_cancel() {
+        let mut dialog = ConfirmDialog::destructive("Delete", "Delete hello?", "Delete", Confirmed::DeleteNote);
+        assert_eq!(dialog.handle_key(&key(KeyCode::Enter)), Some(Confirmed::Cancel));
+        assert_eq!(dialog.handle_key(&key(KeyCode::Left)), None);
+        assert_eq!(dialog.handle_key(&key(KeyCode::Enter)), Some(Confirmed::DeleteNote));
+        assert_eq!(dialog.handle_key(&key(KeyCode::Char('Y'))), Some(Confirmed::DeleteNote));
+        assert_eq!(dialog.handle_key(&key(KeyCode::Char('q'))), None);
+        assert_eq!(dialog.handle_key(&key(KeyCode::Esc)), Some(Confirmed::Cancel));
+    }
+
+    #[test]
+    fn confirm_buttons_fit_dialog() {
+        let dialog = ConfirmDialog::destructive("Delete", "Delete hello?", "Delete", Confirmed::DeleteNote);
+        let buttons = dialog.buttons(Rect::new(0, 0, 80, 24));
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0].y, buttons[1].y);
+        assert!(buttons[0].x + buttons[0].width < buttons[1].x);
+    }
+
+    #[test]
+    fn edits_mark_note_modified() {
+        let app = App { saved: Note::new("repo", "python", "hello", "print(1)"), ..Default::default() };
+        let mut note = app.saved.clone();
+        assert!(!app.is_modified(&note));
+        note.contents.push('\n');
+        assert!(app.is_modified(&note));
+    }
+
+    #[test]
+    fn new_note_from_template_is_modified() {
+        let blank = Note::new("repo", "", "", "");
+        let app = App { saved: blank.clone(), ..Default::default() };
+        assert!(!app.is_modified(&blank));
+        let mut note = Note::new("repo", "python", "", "#!/usr/bin/env python3\n");
+        let app = App { saved: note.clone(), ..Default::default() };
+        assert!(app.is_modified(&note));
+        note.contents.clear();
+        assert!(!App { saved: note.clone(), ..Default::default() }.is_modified(&note));
+    }
+}
+
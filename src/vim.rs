@@ -0,0 +1,475 @@
+use std::fmt;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::input::TextCursor;
+use crate::key::Action;
+
+// sub-modes of the contents editor when the vim keymap is enabled
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl fmt::Display for VimMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VimMode::Normal => write!(f, "-- NORMAL --"),
+            VimMode::Insert => write!(f, "-- INSERT --"),
+            VimMode::Visual => write!(f, "-- VISUAL --"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VimState {
+    pub mode: VimMode,
+    // first key of `dd`, `yy` and `gg`
+    pending: Option<char>,
+    // text typed after `:`
+    pub command_line: Option<String>,
+}
+
+impl Default for VimState {
+    fn default() -> VimState {
+        VimState { mode: VimMode::Normal, pending: None, command_line: None }
+    }
+}
+
+// what the caller has to do after a key went through the vim layer
+#[derive(Debug, PartialEq)]
+pub enum VimOutcome {
+    // not a vim key, run it through the keymap
+    Unhandled,
+    Handled,
+    Yank(String),
+    // paste the clipboard, `true` puts whole lines below the cursor line
+    Paste(bool),
+    Perform(Vec<Action>),
+    Error(String),
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// start of the next word, like vim's `w`
+pub fn word_forward(text: &str, position: usize) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = position;
+    if i < chars.len() && !chars[i].is_whitespace() {
+        let word = is_word_char(chars[i]);
+        while i < chars.len() && !chars[i].is_whitespace() && is_word_char(chars[i]) == word {
+            i += 1;
+        }
+    }
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+// start of the current or previous word, like vim's `b`
+pub fn word_backward(text: &str, position: usize) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = position.min(chars.len());
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    if i > 0 {
+        let word = is_word_char(chars[i - 1]);
+        while i > 0 && !chars[i - 1].is_whitespace() && is_word_char(chars[i - 1]) == word {
+            i -= 1;
+        }
+    }
+    i
+}
+
+// char range of the cursor line including its line break
+fn line_range(text: &str, cursor: &TextCursor) -> (usize, usize) {
+    let (line, _) = cursor.line_col(text);
+    let start = TextCursor::index_of(text, line, 0);
+    let end = TextCursor::index_of(text, line, usize::MAX);
+    (start, end)
+}
+
+fn line_text(text: &str, cursor: &TextCursor) -> String {
+    let (line, _) = cursor.line_col(text);
+    format!("{}\n", text.split('\n').nth(line).unwrap_or(""))
+}
+
+fn delete_line(buffer: &mut String, cursor: &mut TextCursor) {
+    let (start, end) = line_range(buffer, cursor);
+    let len = buffer.chars().count();
+    // take the line break after the line, or before it on the last line
+    let (start, end) = match (end < len, start > 0) {
+        (true, _) => (start, end + 1),
+        (false, true) => (start - 1, end),
+        (false, false) => (start, end),
+    };
+    cursor.move_to(buffer, start, false);
+    cursor.move_to(buffer, end, true);
+    cursor.cut(buffer);
+    let (line, _) = cursor.line_col(buffer);
+    cursor.move_to(buffer, TextCursor::index_of(buffer, line, 0), false);
+}
+
+// what a normal mode key does, `Motion` keys go through `VimState::motion`
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Op {
+    Motion,
+    Top,
+    DeleteChar,
+    DeleteLine,
+    YankLine,
+    Paste(bool),
+    Visual,
+    Insert,
+    Append,
+    InsertHome,
+    AppendEnd,
+    OpenBelow,
+    OpenAbove,
+    Command,
+}
+
+// the normal mode keys of the contents editor, `handle_key` runs them and the help lists them
+const NORMAL_KEYS: &[(&str, Op, &str)] = &[
+    ("h", Op::Motion, "Left"),
+    ("l", Op::Motion, "Right"),
+    ("j", Op::Motion, "Down"),
+    ("k", Op::Motion, "Up"),
+    ("w", Op::Motion, "Next word"),
+    ("b", Op::Motion, "Previous word"),
+    ("0", Op::Motion, "Start of the line"),
+    ("$", Op::Motion, "End of the line"),
+    ("gg", Op::Top, "Start of the contents"),
+    ("G", Op::Motion, "End of the contents"),
+    ("x", Op::DeleteChar, "Delete char"),
+    ("dd", Op::DeleteLine, "Cut line"),
+    ("yy", Op::YankLine, "Copy line"),
+    ("p", Op::Paste(true), "Paste after"),
+    ("P", Op::Paste(false), "Paste before"),
+    ("v", Op::Visual, "Visual mode, y copies and d cuts"),
+    ("i", Op::Insert, "Insert"),
+    ("a", Op::Append, "Insert after the cursor"),
+    ("I", Op::InsertHome, "Insert at the start of the line"),
+    ("A", Op::AppendEnd, "Insert at the end of the line"),
+    ("o", Op::OpenBelow, "New line below"),
+    ("O", Op::OpenAbove, "New line above"),
+    (":", Op::Command, "Command line"),
+];
+
+// the commands typed after `:`
+const COMMANDS: &[(&str, &[Action], &str)] = &[
+    ("w", &[Action::Save], "Save"),
+    ("q", &[Action::Quit], "Quit"),
+    ("q!", &[Action::ForceQuit], "Quit without saving"),
+    ("wq", &[Action::Save, Action::Quit], "Save and quit"),
+    ("x", &[Action::Save, Action::Quit], "Save and quit"),
+];
+
+// shown in the hint bar while editing in normal mode
+const HINTS: &[&str] = &["i", "dd", "p", ":w", ":q"];
+
+pub fn parse_command(command: &str) -> Result<Vec<Action>, String> {
+    let command = command.trim();
+    match COMMANDS.iter().find(|(name, _, _)| *name == command) {
+        Some((_, actions, _)) => Ok(actions.to_vec()),
+        None => Err(format!("Not an editor command: {}", command))
+    }
+}
+
+// every key and command with its description, for the help popup
+pub fn help() -> Vec<(String, &'static str)> {
+    NORMAL_KEYS.iter()
+        .map(|(keys, _, description)| (String::from(*keys), *description))
+        .chain(COMMANDS.iter().map(|(name, _, description)| (format!(":{}", name), *description)))
+        .collect()
+}
+
+pub fn hints() -> Vec<(String, &'static str)> {
+    help().into_iter().filter(|(keys, _)| HINTS.contains(&keys.as_str())).collect()
+}
+
+impl VimState {
+    pub fn start_command(&mut self) {
+        self.command_line = Some(String::new());
+    }
+
+    // keys typed after `:`, both in the list and in the editor
+    pub fn handle_command_key(&mut self, key: &KeyEvent) -> VimOutcome {
+        let command = match self.command_line.as_mut() {
+            Some(command) => command,
+            None => return VimOutcome::Unhandled
+        };
+        match key.code {
+            KeyCode::Char(c) => command.push(c),
+            KeyCode::Backspace if command.is_empty() => self.command_line = None,
+            KeyCode::Backspace => {
+                command.pop();
+            }
+            KeyCode::Esc => self.command_line = None,
+            KeyCode::Enter => {
+                let command = self.command_line.take().unwrap_or_default();
+                return match parse_command(&command) {
+                    Ok(actions) => VimOutcome::Perform(actions),
+                    Err(err) => VimOutcome::Error(err)
+                };
+            }
+            _ => {}
+        }
+        VimOutcome::Handled
+    }
+
+    fn motion(&mut self, c: char, buffer: &str, cursor: &mut TextCursor, select: bool) -> bool {
+        match c {
+            'h' => cursor.move_left(buffer, select),
+            'l' => cursor.move_right(buffer, select),
+            'j' => cursor.move_down(buffer, select),
+            'k' => cursor.move_up(buffer, select),
+            '0' => cursor.move_home(buffer, select),
+            '$' => cursor.move_end(buffer, select),
+            'w' => cursor.move_to(buffer, word_forward(buffer, cursor.position), select),
+            'b' => cursor.move_to(buffer, word_backward(buffer, cursor.position), select),
+            'G' => cursor.move_to(buffer, buffer.chars().count(), select),
+            _ => return false
+        }
+        true
+    }
+
+    fn arrow(key: &KeyEvent) -> Option<char> {
+        match key.code {
+            KeyCode::Left => Some('h'),
+            KeyCode::Right => Some('l'),
+            KeyCode::Down => Some('j'),
+            KeyCode::Up => Some('k'),
+            KeyCode::Home => Some('0'),
+            KeyCode::End => Some('$'),
+            _ => None
+        }
+    }
+
+    // keys of the contents editor
+    pub fn handle_key(&mut self, key: &KeyEvent, buffer: &mut String, cursor: &mut TextCursor) -> VimOutcome {
+        if self.command_line.is_some() {
+            return self.handle_command_key(key);
+        }
+        cursor.clamp(buffer);
+        let plain = !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match self.mode {
+            VimMode::Insert => match key.code {
+                KeyCode::Esc => {
+                    self.mode = VimMode::Normal;
+                    let (_, col) = cursor.line_col(buffer);
+                    if col > 0 {
+                        cursor.move_left(buffer, false);
+                    }
+                    VimOutcome::Handled
+                }
+                _ => VimOutcome::Unhandled
+            },
+            VimMode::Visual => {
+                let c = match (key.code, plain) {
+                    (KeyCode::Char(c), true) => c,
+                    (KeyCode::Esc, _) => 'v',
+                    _ => match VimState::arrow(key) {
+                        Some(c) => c,
+                        None => return VimOutcome::Unhandled
+                    }
+                };
+                if self.motion(c, buffer, cursor, true) {
+                    return VimOutcome::Handled;
+                }
+                match c {
+                    'v' => {
+                        self.mode = VimMode::Normal;
+                        cursor.anchor = None;
+                        VimOutcome::Handled
+                    }
+                    'y' => {
+                        self.mode = VimMode::Normal;
+                        let text = cursor.selected_text(buffer).unwrap_or_default();
+                        cursor.anchor = None;
+                        VimOutcome::Yank(text)
+                    }
+                    'd' | 'x' => {
+                        self.mode = VimMode::Normal;
+                        VimOutcome::Yank(cursor.cut(buffer).unwrap_or_default())
+                    }
+                    _ => VimOutcome::Handled
+                }
+            }
+            VimMode::Normal => {
+                let c = match (key.code, plain) {
+                    (KeyCode::Char(c), true) => c,
+                    (KeyCode::Esc, _) if self.pending.is_some() => {
+                        self.pending = None;
+                        return VimOutcome::Handled;
+                    }
+                    _ => match VimState::arrow(key) {
+                        Some(c) => c,
+                        None => return VimOutcome::Unhandled
+                    }
+                };
+                let (keys, first) = match self.pending.take() {
+                    Some(pending) => (format!("{}{}", pending, c), false),
+                    None => (c.to_string(), true)
+                };
+                let op = match NORMAL_KEYS.iter().find(|(k, _, _)| *k == keys) {
+                    Some((_, op, _)) => *op,
+                    // first key of `dd`, `yy` or `gg`
+                    None if first && NORMAL_KEYS.iter().any(|(k, _, _)| k.len() > 1 && k.starts_with(c)) => {
+                        self.pending = Some(c);
+                        return VimOutcome::Handled;
+                    }
+                    None => return VimOutcome::Handled
+                };
+                match op {
+                    Op::Motion => {
+                        self.motion(c, buffer, cursor, false);
+                    }
+                    Op::Top => cursor.move_to(buffer, 0, false),
+                    Op::DeleteChar => cursor.delete(buffer),
+                    Op::DeleteLine => {
+                        let text = line_text(buffer, cursor);
+                        delete_line(buffer, cursor);
+                        return VimOutcome::Yank(text);
+                    }
+                    Op::YankLine => return VimOutcome::Yank(line_text(buffer, cursor)),
+                    Op::Paste(below) => return VimOutcome::Paste(below),
+                    Op::Visual => {
+                        self.mode = VimMode::Visual;
+                        cursor.anchor = Some(cursor.position);
+                    }
+                    Op::Insert => self.mode = VimMode::Insert,
+                    Op::Append => {
+                        self.mode = VimMode::Insert;
+                        cursor.move_right(buffer, false);
+                    }
+                    Op::InsertHome => {
+                        self.mode = VimMode::Insert;
+                        cursor.move_home(buffer, false);
+                    }
+                    Op::AppendEnd => {
+                        self.mode = VimMode::Insert;
+                        cursor.move_end(buffer, false);
+                    }
+                    Op::OpenBelow => {
+                        self.mode = VimMode::Insert;
+                        cursor.move_end(buffer, false);
+                        cursor.insert(buffer, "\n");
+                    }
+                    Op::OpenAbove => {
+                        self.mode = VimMode::Insert;
+                        cursor.move_home(buffer, false);
+                        cursor.insert(buffer, "\n");
+                        cursor.move_left(buffer, false);
+                    }
+                    Op::Command => self.start_command(),
+                }
+                VimOutcome::Handled
+            }
+        }
+    }
+}
+
+// put yanked lines below the cursor line, other text behind the cursor
+pub fn paste(buffer: &mut String, cursor: &mut TextCursor, text: &str, below: bool) {
+    cursor.clamp(buffer);
+    cursor.anchor = None;
+    match (text.ends_with('\n'), below) {
+        (true, true) => {
+            cursor.move_end(buffer, false);
+            let line = text.trim_end_matches('\n');
+            cursor.insert(buffer, &format!("\n{}", line));
+            cursor.move_home(buffer, false);
+        }
+        (true, false) => {
+            cursor.move_home(buffer, false);
+            let start = cursor.position;
+            cursor.insert(buffer, text);
+            cursor.move_to(buffer, start, false);
+        }
+        (false, _) => {
+            if below && !buffer.is_empty() {
+                cursor.move_right(buffer, false);
+            }
+            cursor.insert(buffer, text);
+            cursor.move_left(buffer, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use super::{help, hints, paste, parse_command, word_backward, word_forward, VimMode, VimOutcome, VimState};
+    use crate::input::TextCursor;
+    use crate::key::Action;
+
+    fn press(vim: &mut VimState, keys: &str, buffer: &mut String, cursor: &mut TextCursor) -> VimOutcome {
+        let mut outcome = VimOutcome::Handled;
+        for c in keys.chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            outcome = vim.handle_key(&key, buffer, cursor);
+        }
+        outcome
+    }
+
+    #[test]
+    fn word_motions() {
+        let text = "let x = foo.bar;";
+        assert_eq!(word_forward(text, 0), 4);
+        assert_eq!(word_forward(text, 8), 11);
+        assert_eq!(word_backward(text, 11), 8);
+        assert_eq!(word_backward(text, 4), 0);
+    }
+
+    #[test]
+    fn dd_and_p_move_lines() {
+        let mut vim = VimState::default();
+        let mut buffer = String::from("one\ntwo\nthree");
+        let mut cursor = TextCursor { position: 0, anchor: None };
+        assert_eq!(press(&mut vim, "dd", &mut buffer, &mut cursor), VimOutcome::Yank(String::from("one\n")));
+        assert_eq!(buffer, "two\nthree");
+        paste(&mut buffer, &mut cursor, "one\n", true);
+        assert_eq!(buffer, "two\none\nthree");
+    }
+
+    #[test]
+    fn insert_and_escape() {
+        let mut vim = VimState::default();
+        let mut buffer = String::from("ab");
+        let mut cursor = TextCursor { position: 0, anchor: None };
+        press(&mut vim, "A", &mut buffer, &mut cursor);
+        assert_eq!(vim.mode, VimMode::Insert);
+        assert_eq!(cursor.position, 2);
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        vim.handle_key(&esc, &mut buffer, &mut cursor);
+        assert_eq!(vim.mode, VimMode::Normal);
+        assert_eq!(cursor.position, 1);
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("wq"), Ok(vec![Action::Save, Action::Quit]));
+        assert_eq!(parse_command("q!"), Ok(vec![Action::ForceQuit]));
+        assert!(parse_command("e foo").is_err());
+    }
+
+    #[test]
+    fn help_lists_keys_and_commands() {
+        let keys: Vec<String> = help().into_iter().map(|(keys, _)| keys).collect();
+        for key in ["dd", "yy", "p", "w", "b", "v", ":w", ":q", ":wq", ":x"] {
+            assert!(keys.iter().any(|k| k == key), "{}", key);
+        }
+        assert_eq!(hints().len(), 5);
+        let mut vim = VimState::default();
+        let mut buffer = String::from("one two");
+        let mut cursor = TextCursor { position: 0, anchor: None };
+        press(&mut vim, "gxw", &mut buffer, &mut cursor);
+        assert_eq!((buffer.as_str(), cursor.position), ("one two", 4));
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command as Process;
+use assert_cmd::Command;
+
+// a snippet repository without a remote and a config pointing at it
+fn library(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    let repo = dir.join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    Process::new("git").arg("-C").arg(&repo).args(["init", "-q", "-b", "main"]).status().unwrap();
+    fs::write(dir.join("config.toml"), format!("repository = {:?}\n\n[sync]\nenabled = false\n", repo.display().to_string())).unwrap();
+    dir
+}
+
+fn snippet(dir: &Path) -> Command {
+    let mut command = Command::cargo_bin("snippet_rustui").unwrap();
+    command.arg("--config").arg(dir.join("config.toml"))
+        .env_remove("SNIPPET_REPO")
+        .env_remove("SNIPPET_PROFILE")
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com");
+    command
+}
+
+#[test]
+fn add_list_show() {
+    let dir = library("add");
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo hi\n").assert().success();
+    snippet(&dir).args(["add", "--title", "hello", "--lang", "python"]).write_stdin("print(1)").assert().success();
+    snippet(&dir).arg("list").assert().success().stdout("python/hello\nsh/greet\n");
+    snippet(&dir).args(["list", "--lang", "SH"]).assert().success().stdout("sh/greet\n");
+    snippet(&dir).args(["show", "greet"]).assert().success().stdout("echo hi\n");
+    snippet(&dir).args(["show", "python/hello"]).assert().success().stdout("print(1)\n");
+    snippet(&dir).args(["search", "gret"]).assert().success().stdout("sh/greet\n");
+
+    let log = Process::new("git").arg("-C").arg(dir.join("repo")).args(["log", "--format=%s"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&log.stdout), "update: python, hello\nupdate: sh, greet\n");
+}
+
+#[test]
+fn exit_codes() {
+    let dir = library("codes");
+    snippet(&dir).args(["show", "missing"]).assert().code(3);
+    snippet(&dir).args(["search", "missing"]).assert().code(3);
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo hi").assert().success();
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo ho").assert().code(5);
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh", "--force"]).write_stdin("echo ho").assert().success();
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "bash"]).write_stdin("echo hey").assert().success();
+    snippet(&dir).args(["show", "greet"]).assert().code(4);
+    snippet(&dir).args(["rm", "greet", "--lang", "bash"]).assert().success();
+    snippet(&dir).args(["show", "greet"]).assert().success().stdout("echo ho\n");
+    snippet(&dir).args(["add", "--title", "", "--lang", "sh"]).write_stdin("").assert().code(2);
+    snippet(&dir).arg("frobnicate").assert().code(2);
+    snippet(&dir).arg("sync").assert().success();
+}
+
+#[test]
+fn names_stay_inside_the_library() {
+    let dir = library("names");
+    snippet(&dir).args(["add", "--title", "../../x", "--lang", "sh"]).write_stdin("ls").assert().code(2);
+    snippet(&dir).args(["add", "--title", "/etc/foo", "--lang", "sh"]).write_stdin("ls").assert().code(2);
+    snippet(&dir).args(["add", "--title", "x", "--lang", ".."]).write_stdin("ls").assert().code(2);
+    assert!(!dir.join("x.md").exists());
+    // a file where the language folder belongs
+    fs::write(dir.join("repo/sh"), "").unwrap();
+    snippet(&dir).args(["add", "--title", "list", "--lang", "sh"]).write_stdin("ls").assert().code(1);
+}
+
+#[test]
+fn requires_a_repository() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli").join("empty");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), "").unwrap();
+    snippet(&dir).arg("list").assert().code(1);
+}
+
+#[test]
+fn json_output() {
+    let dir = library("json");
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo hi\n").assert().success();
+    snippet(&dir).args(["add", "--title", "hello", "--lang", "python"]).write_stdin("print(1)").assert().success();
+
+    let output = snippet(&dir).args(["list", "--json"]).output().unwrap();
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["version"], 1);
+    let snippets = document["snippets"].as_array().unwrap();
+    assert_eq!(snippets.len(), 2);
+    assert_eq!(snippets[0]["id"], "python/hello");
+    assert_eq!(snippets[1]["contents"], "echo hi\n");
+    assert_eq!(snippets[1]["tags"], serde_json::json!([]));
+    assert!(snippets[1]["modified"].is_u64());
+
+    let output = snippet(&dir).args(["search", "gret", "--jsonl"]).output().unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout).lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!((lines[0]["version"].as_u64(), lines[0]["id"].as_str()), (Some(1), Some("sh/greet")));
+
+    let output = snippet(&dir).args(["show", "greet", "--json"]).output().unwrap();
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["snippets"][0]["title"], "greet");
+    snippet(&dir).args(["show", "greet", "--json", "--jsonl"]).assert().code(2);
+}
+
+#[test]
+fn import_in_one_commit() {
+    let dir = library("import");
+    snippet(&dir).args(["add", "--title", "pods", "--lang", "sh"]).write_stdin("kubectl get pods").assert().success();
+    let gist = dir.join("gist");
+    fs::create_dir_all(gist.join(".git")).unwrap();
+    fs::write(gist.join(".git").join("config"), "[core]").unwrap();
+    fs::write(gist.join("hello.py"), "print(1)\n").unwrap();
+    fs::write(gist.join("deploy.sh"), "./deploy.sh prod\n").unwrap();
+    let pet = dir.join("snippet.toml");
+    fs::write(&pet, "[[snippets]]\ndescription = \"list pods\"\ncommand = \"kubectl get pods\"\n").unwrap();
+
+    snippet(&dir).arg("import").arg(&gist).arg("--dry-run").assert().success().stdout("+ sh/deploy\n+ python/hello\n");
+    snippet(&dir).arg("list").assert().success().stdout("sh/pods\n");
+    snippet(&dir).arg("import").arg(&gist).assert().success();
+    snippet(&dir).arg("import").arg(&gist).assert().success().stdout("= sh/deploy (same as sh/deploy)\n= python/hello (same as python/hello)\n");
+    snippet(&dir).arg("import").arg(&pet).assert().success().stdout("= sh/list pods (same as sh/pods)\n");
+    snippet(&dir).arg("list").assert().success().stdout("python/hello\nsh/deploy\nsh/pods\n");
+
+    let log = Process::new("git").arg("-C").arg(dir.join("repo")).args(["log", "--format=%s"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&log.stdout), "import: 2 snippets from gist\nupdate: sh, pods\n");
+    snippet(&dir).arg("import").arg(dir.join("missing")).assert().code(3);
+
+    // nothing stays behind when one of the snippets cannot be written
+    let more = dir.join("more");
+    fs::create_dir_all(&more).unwrap();
+    fs::write(more.join("a.sh"), "echo a\n").unwrap();
+    fs::write(more.join("b.rb"), "puts 1\n").unwrap();
+    fs::write(dir.join("repo/ruby"), "").unwrap();
+    snippet(&dir).arg("import").arg(&more).assert().code(1);
+    assert!(!dir.join("repo/sh/a.md").exists());
+
+    // a broken file of a folder is skipped, the others are imported
+    let pets = dir.join("pets");
+    fs::create_dir_all(&pets).unwrap();
+    fs::write(pets.join("a.toml"), "[[snippets]]\ndescription = \"uptime\"\ncommand = \"uptime\"\n").unwrap();
+    fs::write(pets.join("b.toml"), "[[snippets]\n").unwrap();
+    let output = snippet(&dir).arg("import").arg(&pets).args(["--format", "pet", "--dry-run"]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.starts_with("! ") && stdout.contains("b.toml") && stdout.ends_with("+ sh/uptime\n"), "{}", stdout);
+}
+
+#[test]
+fn export_formats() {
+    let dir = library("export");
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo {{name:world}}").assert().success();
+    let out = dir.join("out");
+    snippet(&dir).arg("export").arg("vscode").arg(out.join("vscode")).assert().success();
+    let vscode: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("vscode").join("shellscript.json")).unwrap()).unwrap();
+    assert_eq!(vscode["greet"]["body"], serde_json::json!(["echo ${1:world}"]));
+    snippet(&dir).arg("export").arg("markdown").arg(out.join("snippets.md")).assert().success();
+    assert!(fs::read_to_string(out.join("snippets.md")).unwrap().contains("## sh\n\n### greet\n\n```sh\necho {{name:world}}\n```\n"));
+    snippet(&dir).arg("export").arg("html").arg(out.join("site")).assert().success();
+    assert!(fs::read_to_string(out.join("site").join("index.html")).unwrap().contains("<h2>greet</h2>"));
+    let index: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("site").join("search-index.json")).unwrap()).unwrap();
+    assert_eq!(index[0]["title"], "greet");
+}
+
+#[test]
+fn shell_init() {
+    // works before any repository is configured
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli").join("shell");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), "").unwrap();
+    let output = snippet(&dir).args(["shell-init", "bash"]).output().unwrap();
+    assert!(output.status.success());
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.contains("snippet_rustui pick </dev/tty)"));
+    assert!(script.contains("bind -m emacs-standard -x"));
+    snippet(&dir).args(["shell-init", "tcsh"]).assert().code(2);
+}
+
+#[test]
+fn skips_broken_files() {
+    let dir = library("broken");
+    snippet(&dir).args(["add", "--title", "greet", "--lang", "sh"]).write_stdin("echo hi").assert().success();
+    fs::write(dir.join("repo/sh/conflict.md"), "<<<<<<< HEAD\n{\"title\": \"conflict\"}\n").unwrap();
+    let out = snippet(&dir).arg("list").assert().success().stdout("sh/greet\n").get_output().clone();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Skipped ") && stderr.contains("conflict.md: not a snippet"), "{}", stderr);
+}